@@ -4,7 +4,9 @@ pub mod lie_exchange;
 mod models;
 pub mod network;
 pub mod packet;
+pub mod rib;
 mod socket;
+pub mod spf;
 pub mod tie_exchange;
 pub mod topology;
 mod wrapper;
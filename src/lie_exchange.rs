@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{BTreeSet, HashMap, VecDeque},
     io,
     net::IpAddr,
     time::{Duration, Instant},
@@ -10,8 +10,9 @@ use serde::Serialize;
 use crate::{
     models::{
         common::{
-            self, LinkIDType, MTUSizeType, UDPPortType, DEFAULT_BANDWIDTH, DEFAULT_LIE_HOLDTIME,
-            DEFAULT_ZTP_HOLDTIME, ILLEGAL_SYSTEM_I_D, MULTIPLE_NEIGHBORS_LIE_HOLDTIME_MULTIPLER,
+            self, HierarchyIndications, LinkIDType, MTUSizeType, UDPPortType, DEFAULT_LIE_HOLDTIME,
+            DEFAULT_MTU_SIZE, DEFAULT_ZTP_HOLDTIME, ILLEGAL_SYSTEM_I_D,
+            MULTIPLE_NEIGHBORS_LIE_HOLDTIME_MULTIPLER,
         },
         encoding::{
             self, LIEPacket, PacketHeader, ProtocolPacket, PROTOCOL_MAJOR_VERSION,
@@ -46,9 +47,10 @@ pub struct LieStateMachine {
     level: Option<Level>,
     /// from spec:  Set of nodes offering HAL VOLs
     /// This, along with `highest_available_level` and `highest_adjacency_threeway` are set by
-    /// LieEvent::HAL/HAT/HALSChanged and are sent by the ZTP FSM usually. For some reason, only the
-    /// HAT seems to be used in the LIE FSM, but it is included for completeness. Note that if ZTP
-    /// is not active, then the HAT and HAL will always be Level::Undefined (and hence have no effect).
+    /// LieEvent::HAL/HAT/HALSChanged and are sent by the ZTP FSM usually. The HAT and HALS are used
+    /// by PROCESS_LIE to decide which neighbors a leaf may form adjacencies with, while the HAL is
+    /// included for completeness. Note that if no offers have been received, then the HAT and HAL
+    /// are undefined and the HALS is empty (and hence they have no effect).
     highest_available_level_systems: HALS,
     // from spec: Highest defined level value seen from all VOLs received.
     highest_available_level: Option<Level>,
@@ -69,6 +71,10 @@ pub struct LieStateMachine {
     /// The time at which the multiple neighbors timer was started
     #[serde(skip)]
     multiple_neighbors_timer: Timer,
+    /// The northbound neighbors elected as flood repeaters by this node, as set by
+    /// LieEvent::FloodLeadersChanged. This determines the `you_are_flood_repeater` value sent to
+    /// the neighbor in the LIE. This is None until the first election has run.
+    flood_repeaters: Option<BTreeSet<SystemID>>,
 }
 
 impl LieStateMachine {
@@ -81,7 +87,7 @@ impl LieStateMachine {
             external_event_queue: VecDeque::new(),
             chained_event_queue: VecDeque::new(),
             level: configured_level,
-            highest_available_level_systems: HALS,
+            highest_available_level_systems: HALS::default(),
             highest_available_level: None,
             highest_adjacency_threeway: None,
             neighbor: None,
@@ -89,6 +95,7 @@ impl LieStateMachine {
             multiple_neighbors_timer: Timer::new(Duration::from_secs(
                 MULTIPLE_NEIGHBORS_LIE_HOLDTIME_MULTIPLER as u64 * DEFAULT_LIE_HOLDTIME as u64,
             )),
+            flood_repeaters: None,
         }
     }
 
@@ -97,6 +104,16 @@ impl LieStateMachine {
         self.level
     }
 
+    /// Returns false if the neighbor told this node it is not a flood repeater for it, via the
+    /// `you_are_flood_repeater` element of the most recent valid LIE. If the element is absent, the
+    /// node is a flood repeater.
+    pub fn is_flood_repeater(&self) -> bool {
+        match &self.last_valid_lie {
+            Some((_, _, lie_packet)) => lie_packet.you_are_flood_repeater.unwrap_or(true),
+            None => true,
+        }
+    }
+
     /// Process a external events, if there exist any events in the event queue. Note that this
     /// also processes any events pushed by the PUSH procedure, so the `chained_event_queue` will
     /// be empty both before and after this call.
@@ -211,7 +228,7 @@ impl LieStateMachine {
                     LieState::TwoWay
                 }
                 LieEvent::HoldtimeExpired => {
-                    LieStateMachine::expire_offer(ztp_fsm, node_info.system_id);
+                    self.expire_offer(ztp_fsm);
                     LieState::OneWay
                 }
                 LieEvent::HALSChanged(new_hals) => {
@@ -225,7 +242,7 @@ impl LieStateMachine {
                         address,
                         &lie_header,
                         &lie_packet,
-                        node_info.system_id,
+                        node_info,
                         socket.local_link_id,
                         socket.mtu,
                     );
@@ -233,7 +250,7 @@ impl LieStateMachine {
                 }
                 LieEvent::ValidReflection => LieState::ThreeWay,
                 LieEvent::SendLie => {
-                    self.send_lie_procedure(socket, node_info)?; // SEND_LIE
+                    self.send_lie_procedure(socket, node_info, ztp_fsm)?; // SEND_LIE
                     LieState::OneWay
                 }
                 LieEvent::UpdateZTPOffer => {
@@ -250,9 +267,9 @@ impl LieStateMachine {
                     LieState::MultipleNeighborsWait
                 }
                 LieEvent::MTUMismatch => LieState::OneWay,
-                LieEvent::FloodLeadersChanged => {
+                LieEvent::FloodLeadersChanged(flood_repeaters) => {
                     // update `you_are_flood_repeater` LIE elements based on flood leader election results
-                    self.update_you_are_flood_repeater();
+                    self.update_you_are_flood_repeater(flood_repeaters);
                     LieState::OneWay
                 }
                 LieEvent::NeighborDroppedReflection => LieState::OneWay,
@@ -275,7 +292,7 @@ impl LieStateMachine {
                         address,
                         &lie_header,
                         &lie_packet,
-                        node_info.system_id,
+                        node_info,
                         socket.local_link_id,
                         socket.mtu,
                     );
@@ -286,14 +303,14 @@ impl LieStateMachine {
                     LieState::TwoWay
                 }
                 LieEvent::HoldtimeExpired => {
-                    LieStateMachine::expire_offer(ztp_fsm, node_info.system_id);
+                    self.expire_offer(ztp_fsm);
                     LieState::OneWay
                 }
                 LieEvent::MTUMismatch => LieState::OneWay,
                 LieEvent::UnacceptableHeader => LieState::OneWay,
                 LieEvent::ValidReflection => LieState::ThreeWay,
                 LieEvent::SendLie => {
-                    self.send_lie_procedure(socket, node_info)?; // SEND_LIE
+                    self.send_lie_procedure(socket, node_info, ztp_fsm)?; // SEND_LIE
                     LieState::TwoWay
                 }
                 LieEvent::HATChanged(new_hat) => {
@@ -309,13 +326,13 @@ impl LieStateMachine {
                     self.update_level(new_level);
                     LieState::TwoWay
                 }
-                LieEvent::FloodLeadersChanged => {
+                LieEvent::FloodLeadersChanged(flood_repeaters) => {
                     // update `you_are_flood_repeater` LIE elements based on flood leader election results
-                    self.update_you_are_flood_repeater();
+                    self.update_you_are_flood_repeater(flood_repeaters);
                     LieState::TwoWay
                 }
                 LieEvent::NewNeighbor => {
-                    self.send_lie_procedure(socket, node_info)?; // PUSH SendLie event
+                    self.send_lie_procedure(socket, node_info, ztp_fsm)?; // PUSH SendLie event
                     LieState::MultipleNeighborsWait
                 }
                 LieEvent::TimerTick => {
@@ -359,7 +376,7 @@ impl LieStateMachine {
                 LieEvent::NeighborChangedAddress => LieState::OneWay,
                 LieEvent::ValidReflection => LieState::ThreeWay,
                 LieEvent::HoldtimeExpired => {
-                    LieStateMachine::expire_offer(ztp_fsm, node_info.system_id);
+                    self.expire_offer(ztp_fsm);
                     LieState::OneWay
                 }
                 LieEvent::UnacceptableHeader => LieState::OneWay,
@@ -379,7 +396,7 @@ impl LieStateMachine {
                     LieState::OneWay
                 }
                 LieEvent::HALSChanged(new_hals) => {
-                    self.highest_available_level_systems = new_hals;
+                    self.store_hals(new_hals); // store HALS
                     LieState::ThreeWay
                 }
                 LieEvent::TimerTick => {
@@ -403,7 +420,7 @@ impl LieStateMachine {
                         address,
                         &lie_header,
                         &lie_packet,
-                        node_info.system_id,
+                        node_info,
                         socket.local_link_id,
                         socket.mtu,
                     ); // PROCESS_LIE
@@ -411,12 +428,13 @@ impl LieStateMachine {
                 }
                 LieEvent::NeighborChangedLevel => LieState::OneWay,
                 LieEvent::SendLie => {
-                    self.send_lie_procedure(socket, node_info)?; // SEND_LIE
+                    self.send_lie_procedure(socket, node_info, ztp_fsm)?; // SEND_LIE
                     LieState::ThreeWay
                 }
-                LieEvent::FloodLeadersChanged => {
+                LieEvent::FloodLeadersChanged(flood_repeaters) => {
                     // update `you_are_flood_repeater` LIE elements based on flood leader election results, PUSH SendLie
-                    self.update_you_are_flood_repeater();
+                    self.update_you_are_flood_repeater(flood_repeaters);
+                    self.push(LieEvent::SendLie);
                     LieState::ThreeWay
                 }
                 LieEvent::MTUMismatch => LieState::OneWay,
@@ -440,7 +458,7 @@ impl LieStateMachine {
             },
             LieState::MultipleNeighborsWait => match event {
                 LieEvent::HoldtimeExpired => {
-                    LieStateMachine::expire_offer(ztp_fsm, node_info.system_id);
+                    self.expire_offer(ztp_fsm);
                     LieState::MultipleNeighborsWait
                 }
                 LieEvent::LieRcvd(_, _, _) => LieState::MultipleNeighborsWait,
@@ -477,9 +495,9 @@ impl LieStateMachine {
                     self.start_multiple_neighbors_timer();
                     LieState::MultipleNeighborsWait
                 }
-                LieEvent::FloodLeadersChanged => {
+                LieEvent::FloodLeadersChanged(flood_repeaters) => {
                     // update `you_are_flood_repeater` LIE elements based on flood leader election results
-                    self.update_you_are_flood_repeater();
+                    self.update_you_are_flood_repeater(flood_repeaters);
                     LieState::MultipleNeighborsWait
                 }
                 LieEvent::ValidReflection => LieState::MultipleNeighborsWait,
@@ -521,14 +539,15 @@ impl LieStateMachine {
         lie_header: &PacketHeader,
         // The body of the incoming LIE packet.
         lie_packet: &LIEPacket,
-        // The system ID of the socket that received the LIE.
-        system_id: SystemID,
+        // The node which received the LIE.
+        node_info: &NodeInfo,
         // The local link ID of the socket that received the LIE.
         local_link_id: LinkIDType,
         // The MTU of the socket that received the LIE.
         socket_mtu: usize,
     ) {
         tracing::trace!("PROCESS_LIE procedure");
+        let system_id = node_info.system_id;
         let lie_level = lie_header.level.map(|x| x as Level);
 
         // 1. if LIE has major version not equal to this node's *or*
@@ -546,9 +565,12 @@ impl LieStateMachine {
         // not pass the check against IllegalSystemID)
         let lie_sender = SystemID::try_from(lie_header.sender).unwrap();
 
-        if lie_packet.link_mtu_size != Some(socket_mtu as MTUSizeType) {
+        // A LIE without an MTU implies the default MTU.
+        let lie_mtu = lie_packet.link_mtu_size.unwrap_or(DEFAULT_MTU_SIZE);
+        if lie_mtu != socket_mtu as MTUSizeType {
             // 2. if LIE has non matching MTUs
             //    then CLEANUP, PUSH UpdateZTPOffer, PUSH MTUMismatch
+            tracing::warn!(lie_mtu, socket_mtu, "MTU mismatch");
             self.cleanup();
             self.push(LieEvent::UpdateZTPOffer);
             self.push(LieEvent::MTUMismatch);
@@ -575,7 +597,9 @@ impl LieStateMachine {
         // The spec, when defining a "valid LIE" and says "passing all checks for adjacency formation
         // while disregarding all clauses involving level values" (4.2.7.1, Valid Offered Level (VOL))
         self.last_valid_lie = {
-            let mut timer = Timer::new(Duration::from_secs(lie_packet.holdtime as u64));
+            // The holdtime is signed on the wire. A negative holdtime is treated as already expired.
+            let holdtime = u64::try_from(lie_packet.holdtime).unwrap_or(0);
+            let mut timer = Timer::new(Duration::from_secs(holdtime));
             timer.start();
             Some((timer, lie_header.clone(), lie_packet.clone()))
         };
@@ -596,22 +620,29 @@ impl LieStateMachine {
             (Some(our_level), Some(remote_level)) => {
                 let local_is_leaf = our_level == LEAF_LEVEL;
                 let remote_is_leaf = remote_level == LEAF_LEVEL;
-                let allow_east_west = false; // TODO: Section 4.3.9 - East - West connections.
-                let remote_below_hat = match self.highest_adjacency_threeway {
+                // Both nodes must indicate support for Section 4.3.9 - East - West connections.
+                let allow_east_west = node_info.supports_leaf_2_leaf()
+                    && lie_packet.node_capabilities.hierarchy_indications
+                        == Some(HierarchyIndications::LEAF_ONLY_AND_LEAF_2_LEAF_PROCEDURES);
+                let remote_at_hat = match self.highest_adjacency_threeway {
                     // if our HAT is undefined, then we have no adjacencys. Therefore, the remote's
-                    // level can't possibly be below the HAT.
-                    None => false,
+                    // level can't possibly differ from the HAT.
+                    None => true,
                     Some(hat) => remote_level == hat,
                 };
+                // A leaf only forms northbound adjacencies with the nodes offering the HAL, if any
+                // offers have been seen.
+                let remote_in_hals = self.highest_available_level_systems.0.is_empty()
+                    || self.highest_available_level_systems.contains(lie_sender);
                 let level_diff = u8::abs_diff(remote_level, our_level);
 
                 // 6.i. the node is at `leaf_level` value and has no ThreeWay adjacencies already to nodes
                 //      at Highest Adjacency ThreeWay (HAT as defined later in Section 4.2.7.1) with level
                 //      different than the adjacent node
-                if local_is_leaf && !remote_below_hat {
+                if local_is_leaf && !remote_is_leaf && remote_at_hat && remote_in_hals {
                     (
                         true,
-                        "this node is leaf and remote is equal to HAT (or HAT is undefined)",
+                        "this node is leaf and remote is in HALS and equal to HAT (or HAT is undefined)",
                     )
                 }
                 // 6.ii. the node is not at `leaf_level` value and the neighboring node is at `leaf_level` value
@@ -636,6 +667,7 @@ impl LieStateMachine {
                 local_level =? self.level,
                 remote_level =? lie_level,
                 hat =? self.highest_adjacency_threeway,
+                hals =? self.highest_available_level_systems,
                 reason = reason,
                 "rejecting LIE packet (UnacceptableHeader)"
             );
@@ -754,9 +786,14 @@ impl LieStateMachine {
     // SEND_LIE:
     // 1. create and send a new LIE packet reflecting the neighbor if known and valid and
     // 2. setting the necessary `not_a_ztp_offer` variable if level was derived from last
-    //    known neighbor on this interface and
+    //    known neighbor on this interface (or if this node is leaf-only) and
     // 3. setting `you_are_not_flood_repeater` to computed value
-    fn send_lie_procedure(&self, socket: &mut LinkSocket, node_info: &NodeInfo) -> io::Result<()> {
+    fn send_lie_procedure(
+        &self,
+        socket: &mut LinkSocket,
+        node_info: &NodeInfo,
+        ztp_fsm: &ZtpStateMachine,
+    ) -> io::Result<()> {
         let neighbor = match &self.neighbor {
             Some(neighbor) => Some(encoding::Neighbor {
                 originator: neighbor.system_id.into(),
@@ -765,6 +802,14 @@ impl LieStateMachine {
             None => None,
         };
 
+        // Offering our level back to the neighbor we derived it from could cause a ZTP loop.
+        // Leaf-only nodes never offer their level at all.
+        let not_a_ztp_offer = ztp_fsm.is_leaf_only()
+            || match &self.neighbor {
+                Some(neighbor) => ztp_fsm.is_level_derived_from(neighbor.system_id),
+                None => false,
+            };
+
         let header = PacketHeader {
             major_version: PROTOCOL_MAJOR_VERSION,
             minor_version: PROTOCOL_MINOR_VERSION,
@@ -772,30 +817,26 @@ impl LieStateMachine {
             level: self.level.map(|x| x as common::LevelType),
         };
 
-        // TODO: fill in these values with real data, instead of None
         let lie_packet = LIEPacket {
             name: node_info.node_name.clone(),
             local_id: socket.local_link_id as common::LinkIDType,
             flood_port: socket.flood_port() as common::UDPPortType,
             link_mtu_size: Some(socket.mtu as MTUSizeType),
-            link_bandwidth: Some(DEFAULT_BANDWIDTH),
+            link_bandwidth: Some(socket.bandwidth),
             neighbor,
-            pod: None,
-            node_capabilities: encoding::NodeCapabilities {
-                protocol_minor_version: PROTOCOL_MINOR_VERSION,
-                flood_reduction: None,
-                hierarchy_indications: None,
-                auto_evpn_support: None,
-                auto_flood_reflection_support: None,
-            },
-            link_capabilities: None,
+            pod: node_info.pod,
+            node_capabilities: node_info.node_capabilities(),
+            link_capabilities: Some(encoding::LinkCapabilities {
+                bfd: Some(false),
+                ipv4_forwarding_capable: Some(true),
+            }),
             holdtime: DEFAULT_LIE_HOLDTIME,
-            label: None,
-            not_a_ztp_offer: None,
-            you_are_flood_repeater: None,
+            label: socket.label,
+            not_a_ztp_offer: Some(not_a_ztp_offer),
+            you_are_flood_repeater: self.you_are_flood_repeater(),
             you_are_sending_too_quickly: None,
-            instance_name: None,
-            fabric_id: None,
+            instance_name: node_info.instance_name.clone(),
+            fabric_id: node_info.fabric_id,
             auto_evpn_version: None,
             auto_flood_reflection_version: None,
             auto_flood_reflection_cluster_id: None,
@@ -848,6 +889,7 @@ impl LieStateMachine {
 
     // implements "store HALS" from spec
     fn store_hals(&mut self, new_hals: HALS) {
+        tracing::debug!(hals =? new_hals, "storing new HALS");
         self.highest_available_level_systems = new_hals;
     }
 
@@ -857,8 +899,19 @@ impl LieStateMachine {
     }
 
     // implements "update `you_are_flood_repeater` LIE elements based on flood leader election results"
-    fn update_you_are_flood_repeater(&mut self) {
-        todo!()
+    fn update_you_are_flood_repeater(&mut self, flood_repeaters: BTreeSet<SystemID>) {
+        self.flood_repeaters = Some(flood_repeaters);
+    }
+
+    // Only northbound neighbors are told whether they are flood repeaters. Until the first
+    // election has run, the element is left out, which means the neighbor is a flood repeater.
+    fn you_are_flood_repeater(&self) -> Option<bool> {
+        match (&self.neighbor, self.level, &self.flood_repeaters) {
+            (Some(neighbor), Some(level), Some(flood_repeaters)) if neighbor.level > level => {
+                Some(flood_repeaters.contains(&neighbor.system_id))
+            }
+            _ => None,
+        }
     }
 
     // returns true if "if last valid LIE was received more than `holdtime` ago as advertised by neighbor"
@@ -873,14 +926,21 @@ impl LieStateMachine {
     // recently recieved valid LIE packet. Note that this is _not_ affected by HoldtimeExpired events
     // or the CLEANUP procedure.
     fn send_offer(&self, ztp_fsm: &mut ZtpStateMachine) {
-        if let Some((_, header, _)) = &self.last_valid_lie {
-            let level = header.level.map(|x| x as Level);
+        if let Some((timer, header, lie_packet)) = &self.last_valid_lie {
+            // A LIE marked `not_a_ztp_offer` must not be used to derive a level, so it is treated
+            // as if it offered no level at all (which causes PROCESS_OFFER to remove the offer).
+            let level = if lie_packet.not_a_ztp_offer == Some(true) {
+                None
+            } else {
+                header.level.map(|x| x as Level)
+            };
             if let Ok(system_id) = header.sender.try_into() {
                 let offer = Offer {
                     level,
                     system_id,
                     state: self.lie_state,
-                    expired: false,
+                    // The offer lives as long as the LIE it was sent in.
+                    expires_at: timer.deadline().unwrap_or_else(Instant::now),
                 };
 
                 tracing::trace!(offer =? offer, "Sending offer to ZTP FSM");
@@ -893,20 +953,25 @@ impl LieStateMachine {
         }
     }
 
-    /// Expire this link's ZTP offer.
-    fn expire_offer(ztp_fsm: &mut ZtpStateMachine, system_id: SystemID) {
-        ztp_fsm.expire_offer_by_id(system_id);
+    /// Expire the ZTP offer of this link's neighbor (that is, the sender of the most recent valid
+    /// LIE), so that it is removed by the ZTP FSM on its next ShortTic.
+    fn expire_offer(&self, ztp_fsm: &mut ZtpStateMachine) {
+        if let Some((_, header, _)) = &self.last_valid_lie {
+            if let Ok(system_id) = header.sender.try_into() {
+                ztp_fsm.expire_offer_by_id(system_id);
+            }
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Neighbor {
     pub level: Level,
-    address: IpAddr,
+    pub address: IpAddr,
     pub system_id: SystemID,
-    flood_port: UDPPortType,
-    name: Option<String>,
-    local_link_id: LinkIDType,
+    pub flood_port: UDPPortType,
+    pub name: Option<String>,
+    pub local_link_id: LinkIDType,
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
@@ -957,8 +1022,9 @@ pub enum LieEvent {
     MultipleNeighbors,
     /// Multiple neighbors timer expired.
     MultipleNeighborsDone,
-    /// Node's election algorithm determined new set of flood leaders.
-    FloodLeadersChanged,
+    /// Node's election algorithm determined new set of flood leaders. This contains the
+    /// northbound neighbors which were elected as flood repeaters.
+    FloodLeadersChanged(BTreeSet<SystemID>),
     /// Send a LIE out.
     SendLie,
     /// Update this node's ZTP offer. This is sent to the ZTP FSM.
@@ -985,16 +1051,24 @@ impl LieEvent {
             LieEvent::HoldtimeExpired => "HoldtimeExpired",
             LieEvent::MultipleNeighbors => "MultipleNeighbors",
             LieEvent::MultipleNeighborsDone => "MultipleNeighborsDone",
-            LieEvent::FloodLeadersChanged => "FloodLeadersChanged",
+            LieEvent::FloodLeadersChanged(..) => "FloodLeadersChanged",
             LieEvent::SendLie => "SendLie",
             LieEvent::UpdateZTPOffer => "UpdateZTPOffer",
         }
     }
 }
 
-// TODO: I have no idea what this will consist of.
-#[derive(Debug, Clone, Copy, Serialize)]
-pub struct HALS;
+/// The set of nodes offering the HAL (the "HALS" of the spec), that is, the neighbors whose valid
+/// offered level is the highest one seen.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HALS(pub BTreeSet<SystemID>);
+
+impl HALS {
+    /// Returns true if the given system is offering the HAL.
+    pub fn contains(&self, system_id: SystemID) -> bool {
+        self.0.contains(&system_id)
+    }
+}
 
 #[derive(Serialize)]
 pub struct ZtpStateMachine {
@@ -1009,10 +1083,16 @@ pub struct ZtpStateMachine {
     #[serde(skip)]
     holddown_timer: Timer,
     highest_available_level: Option<Level>,
+    highest_available_level_systems: HALS,
     highest_adjacency_threeway: Option<Level>,
     hal_needs_resend: bool,
     hals_needs_resend: bool,
     hat_needs_resend: bool,
+    /// The level most recently sent to the LIE FSMs, which start out at the configured level.
+    advertised_level: Option<Level>,
+    /// Whether LEVEL_COMPUTE has computed results which have not been sent to the LIE FSMs yet
+    /// (which happens on entry into UpdatingClients).
+    results_pending: bool,
     // TODO: this is sort of a hack so that COMPARE_OFFERS and COMPUTE_LEVEL don't both need to
     // do the same work.
     compare_offer_results: CompareOffersResults,
@@ -1020,7 +1100,7 @@ pub struct ZtpStateMachine {
 
 impl ZtpStateMachine {
     pub fn new(configured_level: Option<Level>, leaf_flags: LeafFlags) -> ZtpStateMachine {
-        ZtpStateMachine {
+        let mut ztp_fsm = ZtpStateMachine {
             state: ZtpState::ComputeBestOffer,
             external_event_queue: VecDeque::new(),
             chained_event_queue: VecDeque::new(),
@@ -1029,15 +1109,22 @@ impl ZtpStateMachine {
             offers: HashMap::new(),
             holddown_timer: Timer::new(Duration::from_secs(DEFAULT_ZTP_HOLDTIME as u64)),
             highest_available_level: None,
+            highest_available_level_systems: HALS::default(),
             highest_adjacency_threeway: None,
             hal_needs_resend: false,
             hals_needs_resend: false,
             hat_needs_resend: false,
+            advertised_level: None,
+            results_pending: false,
             compare_offer_results: CompareOffersResults {
                 hal: None,
+                hals: HALS::default(),
                 hat: None,
             },
-        }
+        };
+        // The LIE FSMs start out at the configured (or forced leaf) level.
+        ztp_fsm.advertised_level = ztp_fsm.level();
+        ztp_fsm
     }
 
     /// Process all external events, if there exist any events in the event queue. Note that this
@@ -1109,28 +1196,32 @@ impl ZtpStateMachine {
             } else if new_state == ZtpState::UpdatingClients {
                 // on Entry into UpdatingClients: update all LIE FSMs with computation results
                 // here we sent events, which will be returned and eventually added to all LIE FSMs.
+                self.results_pending = false;
                 if self.hal_needs_resend {
                     events.push(LieEvent::HALChanged(self.highest_available_level));
-                    if let Some(_) = self.highest_available_level {
-                        // TODO: rift-python just directly sets self._derived_level, which means they
-                        // don't issue LevelChanged (which also means that the LIE FSM does not
-                        // reset to OneWay)
-                        tracing::debug!(
-                            new_level =? self.level(),
-                            "Pushing LevelChanged from ZTP FSM"
-                        );
-                        events.push(LieEvent::LevelChanged(self.level()));
-                    }
-
                     self.hal_needs_resend = false;
                 }
+                // LevelChanged resets the adjacencies, so it is only pushed if the level actually
+                // changed (which it never does due to the HAL if a level is configured).
+                if self.level() != self.advertised_level {
+                    // TODO: rift-python just directly sets self._derived_level, which means they
+                    // don't issue LevelChanged (which also means that the LIE FSM does not
+                    // reset to OneWay)
+                    tracing::debug!(
+                        new_level =? self.level(),
+                        "Pushing LevelChanged from ZTP FSM"
+                    );
+                    events.push(LieEvent::LevelChanged(self.level()));
+                    self.advertised_level = self.level();
+                }
                 if self.hat_needs_resend {
                     events.push(LieEvent::HATChanged(self.highest_adjacency_threeway));
                     self.hat_needs_resend = false;
                 }
                 if self.hals_needs_resend {
-                    // TODO: What should a HALS actually look like?
-                    events.push(LieEvent::HALSChanged(HALS));
+                    events.push(LieEvent::HALSChanged(
+                        self.highest_available_level_systems.clone(),
+                    ));
                     self.hals_needs_resend = false;
                 }
             }
@@ -1148,41 +1239,6 @@ impl ZtpStateMachine {
     fn process_ztp_event(&mut self, event: ZtpEvent) -> ZtpState {
         match self.state {
             ZtpState::ComputeBestOffer => match event {
-                ZtpEvent::ChangeLocalConfiguredLevel(new_level) => {
-                    // store configured level
-                    self.store_configured_level(new_level);
-                    ZtpState::ComputeBestOffer
-                }
-                ZtpEvent::BetterHAT => ZtpState::HoldingDown,
-                ZtpEvent::ShortTic => {
-                    // remove expired offers and if holddown timer expired PUSH_EVENT HoldDownExpired
-                    self.remove_expired_offers();
-                    if self.holddown_timer.is_expired() {
-                        self.push(ZtpEvent::HoldDownExpired);
-                    }
-                    ZtpState::HoldingDown
-                }
-                ZtpEvent::NeighborOffer(offer) => {
-                    // PROCESS_OFFER
-                    self.process_offer(offer);
-                    ZtpState::HoldingDown
-                }
-                ZtpEvent::ComputationDone => ZtpState::HoldingDown,
-                ZtpEvent::BetterHAL => ZtpState::HoldingDown,
-                ZtpEvent::LostHAT => ZtpState::HoldingDown,
-                ZtpEvent::LostHAL => ZtpState::HoldingDown,
-                ZtpEvent::HoldDownExpired => {
-                    // PURGE_OFFERS
-                    self.purge_offers();
-                    ZtpState::ComputeBestOffer
-                }
-                ZtpEvent::ChangeLocalHierarchyIndications(new_flags) => {
-                    // store leaf flags
-                    self.store_leaf_flags(new_flags);
-                    ZtpState::ComputeBestOffer
-                }
-            },
-            ZtpState::HoldingDown => match event {
                 ZtpEvent::LostHAT => {
                     self.level_compute(); // LEVEL_COMPUTE
                     ZtpState::ComputeBestOffer
@@ -1226,6 +1282,41 @@ impl ZtpStateMachine {
                     unreachable!("event {} cannot occur in {:?}", event.name(), self.state)
                 }
             },
+            ZtpState::HoldingDown => match event {
+                ZtpEvent::ChangeLocalConfiguredLevel(new_level) => {
+                    // store configured level
+                    self.store_configured_level(new_level);
+                    ZtpState::ComputeBestOffer
+                }
+                ZtpEvent::BetterHAT => ZtpState::HoldingDown,
+                ZtpEvent::ShortTic => {
+                    // remove expired offers and if holddown timer expired PUSH_EVENT HoldDownExpired
+                    self.remove_expired_offers();
+                    if self.holddown_timer.is_expired() {
+                        self.push(ZtpEvent::HoldDownExpired);
+                    }
+                    ZtpState::HoldingDown
+                }
+                ZtpEvent::NeighborOffer(offer) => {
+                    // PROCESS_OFFER
+                    self.process_offer(offer);
+                    ZtpState::HoldingDown
+                }
+                ZtpEvent::ComputationDone => ZtpState::HoldingDown,
+                ZtpEvent::BetterHAL => ZtpState::HoldingDown,
+                ZtpEvent::LostHAT => ZtpState::HoldingDown,
+                ZtpEvent::LostHAL => ZtpState::HoldingDown,
+                ZtpEvent::HoldDownExpired => {
+                    // PURGE_OFFERS
+                    self.purge_offers();
+                    ZtpState::ComputeBestOffer
+                }
+                ZtpEvent::ChangeLocalHierarchyIndications(new_flags) => {
+                    // store leaf flags
+                    self.store_leaf_flags(new_flags);
+                    ZtpState::ComputeBestOffer
+                }
+            },
             ZtpState::UpdatingClients => match event {
                 ZtpEvent::ShortTic => {
                     self.remove_expired_offers(); // remove expired offers
@@ -1279,6 +1370,12 @@ impl ZtpStateMachine {
         let mut events = vec![];
 
         let best_offer = self.offers.values().map(|x| x.level).max().flatten();
+        let best_offer_systems = self
+            .offers
+            .values()
+            .filter(|x| best_offer.is_some() && x.level == best_offer)
+            .map(|x| x.system_id)
+            .collect();
         let best_offer_hat = self
             .offers
             .values()
@@ -1292,20 +1389,29 @@ impl ZtpStateMachine {
             .max()
             .flatten();
 
-        if best_offer.is_some() && self.highest_available_level != best_offer {
+        // Events are only pushed if something actually changed. Note that an undefined level is
+        // lower than any defined level, so if the offers providing the HAL or HAT disappear, then
+        // it is lost. A different set of systems offering the same HAL is treated as a better HAL,
+        // so that the new HALS is computed and passed on to the LIE FSMs.
+        let best_offer_systems = HALS(best_offer_systems);
+        if best_offer > self.highest_available_level
+            || (best_offer == self.highest_available_level
+                && best_offer_systems != self.highest_available_level_systems)
+        {
             events.push(ZtpEvent::BetterHAL);
-        } else {
+        } else if best_offer < self.highest_available_level {
             events.push(ZtpEvent::LostHAL);
         }
 
-        if best_offer_hat.is_some() && self.highest_adjacency_threeway != best_offer_hat {
+        if best_offer_hat > self.highest_adjacency_threeway {
             events.push(ZtpEvent::BetterHAT);
-        } else {
+        } else if best_offer_hat < self.highest_adjacency_threeway {
             events.push(ZtpEvent::LostHAT);
         }
 
         self.compare_offer_results = CompareOffersResults {
             hal: best_offer,
+            hals: best_offer_systems,
             hat: best_offer_hat,
         };
 
@@ -1315,7 +1421,8 @@ impl ZtpStateMachine {
     // Implements the UPDATE_OFFER procedure:
     // store current offer with adjacency holdtime as lifetime and COMPARE_OFFERS,
     // then PUSH according events
-    // TODO: what does "adjacency holdtime" mean?
+    // The adjacency holdtime is the holdtime of the LIE which carried the offer, so the offer
+    // already holds its deadline (see `LieStateMachine::send_offer`).
     fn update_offer(&mut self, offer: Offer) {
         tracing::trace!(offer =? offer, "UPDATE_OFFER procedure");
         self.offers.insert(offer.system_id, offer);
@@ -1334,20 +1441,43 @@ impl ZtpStateMachine {
         let new_hal = self.compare_offer_results.hal;
         let new_hat = self.compare_offer_results.hat;
 
-        if new_hal.is_some() && new_hal != self.highest_available_level {
+        // The HAL and HAT may also be lost, once all of the offers providing them have been removed.
+        if new_hal != self.highest_available_level {
             self.highest_available_level = new_hal;
             self.hal_needs_resend = true;
             anything_changed = true;
         }
 
-        if new_hat.is_some() && new_hat != self.highest_adjacency_threeway {
+        // The HALS may change even if the HAL does not, for instance if another neighbor starts
+        // offering the same level.
+        if self.compare_offer_results.hals != self.highest_available_level_systems {
+            self.highest_available_level_systems = self.compare_offer_results.hals.clone();
+            self.hals_needs_resend = true;
+            anything_changed = true;
+        }
+
+        if new_hat != self.highest_adjacency_threeway {
             self.highest_adjacency_threeway = new_hat;
             self.hat_needs_resend = true;
             anything_changed = true;
         }
 
-        // rift-python appears to push this unconditionally?
+        // The level may also change without the HAL changing, if the configured level or the leaf
+        // flags changed.
+        if self.level() != self.advertised_level {
+            anything_changed = true;
+        }
+
+        // The ComputationDone pushed for earlier results is dropped if the node goes into HoldingDown
+        // before it reaches UpdatingClients. LEVEL_COMPUTE runs again on entry into
+        // ComputeBestOffer once the holddown is over, so ComputationDone is pushed again then.
+        let results_dropped = self.results_pending && self.state == ZtpState::HoldingDown;
         if anything_changed {
+            self.results_pending = true;
+        }
+
+        // rift-python appears to push this unconditionally?
+        if anything_changed || results_dropped {
             self.push(ZtpEvent::ComputationDone);
         }
     }
@@ -1409,8 +1539,18 @@ impl ZtpStateMachine {
     }
 
     // implements "remove expired offers"
+    // Offers expire once the holdtime of the LIE they were received with has passed. If any offers
+    // are removed, then COMPARE_OFFERS is performed and the according events are PUSHed.
     fn remove_expired_offers(&mut self) {
-        self.offers.retain(|_, offer| !offer.expired);
+        let now = Instant::now();
+        let num_offers = self.offers.len();
+        self.offers.retain(|_, offer| offer.expires_at > now);
+        if self.offers.len() != num_offers {
+            tracing::debug!(remaining_offers =? self.offers, "removed expired offers");
+            for event in self.compare_offers() {
+                self.push(event);
+            }
+        }
     }
 
     // implements "if any southbound adjacencies present then update holddown timer
@@ -1426,15 +1566,29 @@ impl ZtpStateMachine {
         }
     }
 
-    // Attempt to expire an offer by the given system ID. Returns false if the ID is not there or if
-    // the offer is already expired, and true otherwise.
+    // Attempt to expire an offer by the given system ID, so that it is removed by the next "remove
+    // expired offers". Returns false if the ID is not there or if the offer is already expired, and
+    // true otherwise.
     pub fn expire_offer_by_id(&mut self, system_id: SystemID) -> bool {
+        let now = Instant::now();
         match self.offers.get_mut(&system_id) {
-            Some(offer) => {
-                offer.expired = true;
+            Some(offer) if offer.expires_at > now => {
+                offer.expires_at = now;
                 true
             }
-            None => false,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the level of this node was derived via ZTP from the offer of the given
+    /// neighbor. That is, no level is configured and the neighbor offers the HAL.
+    pub fn is_level_derived_from(&self, system_id: SystemID) -> bool {
+        if self.configured_level.is_some() || self.is_leaf_only() {
+            return false;
+        }
+        match (self.offers.get(&system_id), self.highest_available_level) {
+            (Some(offer), Some(hal)) => offer.level == Some(hal),
+            _ => false,
         }
     }
 
@@ -1445,8 +1599,17 @@ impl ZtpStateMachine {
         }
     }
 
-    fn level(&self) -> Option<Level> {
-        if self.configured_level == None {
+    /// Returns true if this node is leaf-only, in which case its level is always the leaf level.
+    pub fn is_leaf_only(&self) -> bool {
+        self.leaf_flags.leaf_only
+    }
+
+    /// The level of this node. This is the leaf level if the node is leaf-only, and otherwise
+    /// either the configured level or, if no level is configured, the level derived via ZTP.
+    pub fn level(&self) -> Option<Level> {
+        if self.is_leaf_only() {
+            Some(LEAF_LEVEL)
+        } else if self.configured_level == None {
             self.derived_level()
         } else {
             self.configured_level
@@ -1457,6 +1620,7 @@ impl ZtpStateMachine {
 #[derive(Serialize)]
 struct CompareOffersResults {
     hal: Option<Level>,
+    hals: HALS,
     hat: Option<Level>,
 }
 
@@ -1514,11 +1678,55 @@ pub struct Offer {
     level: Option<Level>,
     system_id: SystemID,
     state: LieState,
-    expired: bool,
+    /// The time after which the offer is no longer valid, as determined by the holdtime of the LIE
+    /// the offer was received with.
+    #[serde(skip)]
+    expires_at: Instant,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct LeafFlags;
+/// The leaf related hierarchy indications of a node. These are configured through the `leaf` and
+/// `leaf-to-leaf` levels (see [crate::topology::Level]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LeafFlags {
+    /// The node is always a leaf. It never derives any other level via ZTP, and never offers its
+    /// level to other nodes.
+    pub leaf_only: bool,
+    /// The node supports the leaf-2-leaf procedures (4.3.9). This requires `leaf_only` to be set as
+    /// well (see [LeafFlags::is_valid]).
+    pub leaf_2_leaf: bool,
+}
+
+impl LeafFlags {
+    /// The hierarchy indications advertised in the node capabilities for these flags.
+    pub fn hierarchy_indications(&self) -> Option<HierarchyIndications> {
+        match (self.leaf_only, self.leaf_2_leaf) {
+            (true, true) => Some(HierarchyIndications::LEAF_ONLY_AND_LEAF_2_LEAF_PROCEDURES),
+            (true, false) => Some(HierarchyIndications::LEAF_ONLY),
+            (false, _) => None,
+        }
+    }
+
+    /// Returns false if `leaf_2_leaf` is set without `leaf_only`, which no topology level maps to.
+    pub fn is_valid(&self) -> bool {
+        self.leaf_only || !self.leaf_2_leaf
+    }
+
+    /// The configured level of a node whose leaf flags change from `self` to `new`, given its
+    /// current configured level. Just like the `leaf` level in the topology, setting `leaf_only`
+    /// configures the leaf level. Clearing `leaf_only` also clears the configured leaf level which
+    /// came with it, so the node derives its level via ZTP again.
+    pub fn configured_level(
+        &self,
+        new: LeafFlags,
+        configured_level: Option<Level>,
+    ) -> Option<Level> {
+        match (self.leaf_only, new.leaf_only) {
+            (false, true) => Some(LEAF_LEVEL),
+            (true, false) => None,
+            _ => configured_level,
+        }
+    }
+}
 
 pub struct Timer {
     start: Option<Instant>,
@@ -1538,6 +1746,12 @@ impl Timer {
         self.start = Some(Instant::now());
     }
 
+    /// Returns the time at which the timer expires, or None if the timer has not been started or
+    /// the expiry time cannot be represented.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.start.and_then(|start| start.checked_add(self.length))
+    }
+
     /// Force the timer to expire, even if the timer still has some time left on it.
     pub fn force_expire(&mut self) {
         self.start = None;
@@ -1552,3 +1766,470 @@ impl Timer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::BTreeSet,
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        models::{
+            common::{self, DEFAULT_LIE_HOLDTIME, DEFAULT_MTU_SIZE},
+            encoding::{
+                LIEPacket, NodeCapabilities, PacketHeader, PROTOCOL_MAJOR_VERSION,
+                PROTOCOL_MINOR_VERSION,
+            },
+        },
+        network::NodeInfo,
+        wrapper::SystemID,
+    };
+
+    use super::{
+        HierarchyIndications, LeafFlags, LieEvent, LieState, LieStateMachine, Offer, ZtpEvent,
+        ZtpState, ZtpStateMachine, HALS, LEAF_LEVEL,
+    };
+
+    fn offer(system_id: i64, level: u8) -> ZtpEvent {
+        ZtpEvent::NeighborOffer(Offer {
+            level: Some(level),
+            system_id: SystemID::try_from(system_id).unwrap(),
+            state: LieState::TwoWay,
+            expires_at: Instant::now() + Duration::from_secs(DEFAULT_LIE_HOLDTIME as u64),
+        })
+    }
+
+    fn node_info(system_id: i64) -> NodeInfo {
+        NodeInfo {
+            node_name: None,
+            configured_level: None,
+            system_id: SystemID::try_from(system_id).unwrap(),
+            pod: None,
+            instance_name: None,
+            fabric_id: None,
+            flood_reduction: false,
+            leaf_flags: LeafFlags::default(),
+        }
+    }
+
+    /// A LIE sent by the given system at the given level.
+    fn lie(sender: i64, level: u8) -> (PacketHeader, LIEPacket) {
+        let header = PacketHeader {
+            major_version: PROTOCOL_MAJOR_VERSION,
+            minor_version: PROTOCOL_MINOR_VERSION,
+            sender,
+            level: Some(level as common::LevelType),
+        };
+        let node_capabilities = NodeCapabilities {
+            protocol_minor_version: PROTOCOL_MINOR_VERSION,
+            flood_reduction: None,
+            hierarchy_indications: None,
+            auto_evpn_support: None,
+            auto_flood_reflection_support: None,
+        };
+        let packet = LIEPacket::new(
+            None,
+            1,
+            common::DEFAULT_TIE_UDP_FLOOD_PORT,
+            None,
+            None,
+            None,
+            None,
+            node_capabilities,
+            None,
+            DEFAULT_LIE_HOLDTIME,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        (header, packet)
+    }
+
+    /// Runs PROCESS_LIE on node 1 and returns the names of the events it pushed.
+    fn process_lie(
+        lie_fsm: &mut LieStateMachine,
+        (header, packet): (PacketHeader, LIEPacket),
+    ) -> Vec<String> {
+        lie_fsm.process_lie_procedure(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            &header,
+            &packet,
+            &node_info(1),
+            0,
+            DEFAULT_MTU_SIZE as usize,
+        );
+        lie_fsm
+            .chained_event_queue
+            .drain(..)
+            .map(|event| event.name().to_string())
+            .collect()
+    }
+
+    /// Returns the last HALS sent to the LIE FSMs by the given events, if any.
+    fn last_hals(events: Vec<LieEvent>) -> Option<HALS> {
+        events.into_iter().rev().find_map(|event| match event {
+            LieEvent::HALSChanged(hals) => Some(hals),
+            _ => None,
+        })
+    }
+
+    fn hals(system_ids: &[i64]) -> HALS {
+        HALS(
+            system_ids
+                .iter()
+                .map(|&system_id| SystemID::try_from(system_id).unwrap())
+                .collect::<BTreeSet<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_hals() {
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        assert_eq!(
+            last_hals(ztp_fsm.process_external_events()),
+            Some(hals(&[1]))
+        );
+
+        // Another neighbor offering the same level joins the HALS, but one offering a lower level
+        // does not.
+        ztp_fsm.push_external_event(offer(2, 5));
+        ztp_fsm.push_external_event(offer(3, 4));
+        assert_eq!(
+            last_hals(ztp_fsm.process_external_events()),
+            Some(hals(&[1, 2]))
+        );
+        assert_eq!(ztp_fsm.level(), Some(4));
+
+        // A higher offer replaces the HALS entirely.
+        ztp_fsm.push_external_event(offer(3, 6));
+        assert_eq!(
+            last_hals(ztp_fsm.process_external_events()),
+            Some(hals(&[3]))
+        );
+    }
+
+    #[test]
+    fn test_remove_expired_offers() {
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        ztp_fsm.process_external_events();
+        // This offer's LIE holdtime has already passed.
+        ztp_fsm.push_external_event(ZtpEvent::NeighborOffer(Offer {
+            level: Some(6),
+            system_id: SystemID::try_from(2).unwrap(),
+            state: LieState::TwoWay,
+            expires_at: Instant::now(),
+        }));
+        ztp_fsm.process_external_events();
+        assert_eq!(ztp_fsm.level(), Some(5));
+
+        // The stale offer is removed on the next ShortTic, and the level is derived from the
+        // remaining neighbor again. As the HAL is lost, the node first holds down, which purges
+        // all offers. Without southbound adjacencies, the holddown is over after the following
+        // ShortTics, and the remaining neighbor keeps sending its offer.
+        ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+        ztp_fsm.process_external_events();
+        assert!(!ztp_fsm.offers.contains_key(&SystemID::try_from(2).unwrap()));
+        for _ in 0..2 {
+            ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+            ztp_fsm.process_external_events();
+        }
+        ztp_fsm.push_external_event(offer(1, 5));
+        ztp_fsm.process_external_events();
+        assert_eq!(ztp_fsm.level(), Some(4));
+    }
+
+    #[test]
+    fn test_configured_level() {
+        // Offers never change a configured level, so the adjacencies are not reset by them.
+        let mut ztp_fsm = ZtpStateMachine::new(Some(2), LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        let events = ztp_fsm.process_external_events();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, LieEvent::LevelChanged(_))));
+        assert_eq!(ztp_fsm.level(), Some(2));
+    }
+
+    #[test]
+    fn test_is_level_derived_from() {
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        ztp_fsm.push_external_event(offer(2, 4));
+        ztp_fsm.process_external_events();
+        assert!(ztp_fsm.is_level_derived_from(SystemID::try_from(1).unwrap()));
+        assert!(!ztp_fsm.is_level_derived_from(SystemID::try_from(2).unwrap()));
+        assert!(!ztp_fsm.is_level_derived_from(SystemID::try_from(3).unwrap()));
+
+        // A configured level is never derived from any neighbor.
+        let mut ztp_fsm = ZtpStateMachine::new(Some(4), LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        ztp_fsm.process_external_events();
+        assert!(!ztp_fsm.is_level_derived_from(SystemID::try_from(1).unwrap()));
+    }
+
+    #[test]
+    fn test_mtu_mismatch() {
+        let mut lie_fsm = LieStateMachine::new(Some(1));
+        let (header, mut packet) = lie(2, 2);
+        packet.link_mtu_size = Some(DEFAULT_MTU_SIZE + 1);
+        let events = process_lie(&mut lie_fsm, (header.clone(), packet.clone()));
+        assert!(events.contains(&"MTUMismatch".to_string()));
+        assert!(!events.contains(&"NewNeighbor".to_string()));
+
+        // A LIE without an MTU implies the default MTU, which matches.
+        packet.link_mtu_size = None;
+        let events = process_lie(&mut lie_fsm, (header, packet));
+        assert!(!events.contains(&"MTUMismatch".to_string()));
+        assert!(events.contains(&"NewNeighbor".to_string()));
+    }
+
+    #[test]
+    fn test_negative_holdtime() {
+        let mut lie_fsm = LieStateMachine::new(Some(1));
+        let (header, mut packet) = lie(2, 2);
+        packet.holdtime = -1;
+        process_lie(&mut lie_fsm, (header, packet));
+
+        // The offer sent with a negative holdtime expires right away instead of overflowing.
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        lie_fsm.send_offer(&mut ztp_fsm);
+        ztp_fsm.process_external_events();
+        ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+        ztp_fsm.process_external_events();
+        assert!(!ztp_fsm.offers.contains_key(&SystemID::try_from(2).unwrap()));
+    }
+
+    #[test]
+    fn test_you_are_flood_repeater() {
+        let mut lie_fsm = LieStateMachine::new(Some(1));
+        process_lie(&mut lie_fsm, lie(2, 2));
+
+        // A fresh northbound adjacency is a flood repeater until the first election has run.
+        assert_eq!(lie_fsm.you_are_flood_repeater(), None);
+        lie_fsm.update_you_are_flood_repeater(BTreeSet::new());
+        assert_eq!(lie_fsm.you_are_flood_repeater(), Some(false));
+        lie_fsm.update_you_are_flood_repeater([SystemID::try_from(2).unwrap()].into());
+        assert_eq!(lie_fsm.you_are_flood_repeater(), Some(true));
+
+        // Southbound neighbors are never told.
+        let mut lie_fsm = LieStateMachine::new(Some(2));
+        process_lie(&mut lie_fsm, lie(3, 1));
+        lie_fsm.update_you_are_flood_repeater(BTreeSet::new());
+        assert_eq!(lie_fsm.you_are_flood_repeater(), None);
+    }
+
+    #[test]
+    fn test_leaf_hat() {
+        // A leaf which already has a ThreeWay adjacency at level 2 only accepts LIEs from nodes at
+        // that level.
+        let mut lie_fsm = LieStateMachine::new(Some(LEAF_LEVEL));
+        lie_fsm.store_hat(Some(2));
+        assert!(process_lie(&mut lie_fsm, lie(3, 1)).contains(&"UnacceptableHeader".to_string()));
+        assert!(process_lie(&mut lie_fsm, lie(2, 2)).contains(&"NewNeighbor".to_string()));
+    }
+
+    #[test]
+    fn test_leaf_only() {
+        let leaf_flags = LeafFlags {
+            leaf_only: true,
+            leaf_2_leaf: false,
+        };
+        let mut ztp_fsm = ZtpStateMachine::new(None, leaf_flags);
+        assert_eq!(ztp_fsm.level(), Some(LEAF_LEVEL));
+
+        // Offers are still tracked, but never change the level, and the level is never offered back.
+        ztp_fsm.push_external_event(offer(1, 5));
+        let events = ztp_fsm.process_external_events();
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, LieEvent::LevelChanged(_))));
+        assert_eq!(ztp_fsm.level(), Some(LEAF_LEVEL));
+        assert!(!ztp_fsm.is_level_derived_from(SystemID::try_from(1).unwrap()));
+        assert_eq!(
+            leaf_flags.hierarchy_indications(),
+            Some(HierarchyIndications::LEAF_ONLY)
+        );
+
+        // Clearing the leaf-only flag goes back to the level derived from the offers.
+        ztp_fsm.push_external_event(ZtpEvent::ChangeLocalHierarchyIndications(
+            LeafFlags::default(),
+        ));
+        let events = ztp_fsm.process_external_events();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, LieEvent::LevelChanged(Some(4)))));
+    }
+
+    #[test]
+    fn test_leaf_flags_configured_level() {
+        let leaf = LeafFlags {
+            leaf_only: true,
+            leaf_2_leaf: false,
+        };
+        let none = LeafFlags::default();
+        assert_eq!(none.configured_level(leaf, None), Some(LEAF_LEVEL));
+        assert_eq!(none.configured_level(leaf, Some(3)), Some(LEAF_LEVEL));
+        assert_eq!(leaf.configured_level(none, Some(LEAF_LEVEL)), None);
+        assert_eq!(none.configured_level(none, Some(3)), Some(3));
+
+        assert!(leaf.is_valid());
+        assert!(none.is_valid());
+        assert!(!LeafFlags {
+            leaf_only: false,
+            leaf_2_leaf: true,
+        }
+        .is_valid());
+    }
+
+    /// Stores an offer from the given neighbor (or removes its offer, if it offers no level) and
+    /// returns the names of the events COMPARE_OFFERS pushes for it. The results are then taken
+    /// over by LEVEL_COMPUTE, as the ZTP FSM does for any of these events.
+    fn compare_offer(
+        ztp_fsm: &mut ZtpStateMachine,
+        system_id: i64,
+        level: Option<u8>,
+        state: LieState,
+    ) -> Vec<String> {
+        let system_id = SystemID::try_from(system_id).unwrap();
+        match level {
+            Some(_) => {
+                let offer = Offer {
+                    level,
+                    system_id,
+                    state,
+                    expires_at: Instant::now() + Duration::from_secs(DEFAULT_LIE_HOLDTIME as u64),
+                };
+                ztp_fsm.offers.insert(system_id, offer);
+            }
+            None => {
+                ztp_fsm.offers.remove(&system_id);
+            }
+        }
+        let events = ztp_fsm.compare_offers();
+        ztp_fsm.level_compute();
+        events
+            .iter()
+            .map(|event| event.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_compare_offers() {
+        use LieState::{ThreeWay, TwoWay};
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+
+        // Increasing offers are better, and so is another neighbor offering the same HAL.
+        assert_eq!(
+            compare_offer(&mut ztp_fsm, 1, Some(5), TwoWay),
+            ["BetterHAL"]
+        );
+        assert_eq!(
+            compare_offer(&mut ztp_fsm, 1, Some(5), ThreeWay),
+            ["BetterHAT"]
+        );
+        assert_eq!(
+            compare_offer(&mut ztp_fsm, 2, Some(6), TwoWay),
+            ["BetterHAL"]
+        );
+        assert_eq!(
+            compare_offer(&mut ztp_fsm, 3, Some(6), TwoWay),
+            ["BetterHAL"]
+        );
+
+        // Offers which change neither the HAL nor the HAT change nothing, and neither does sending
+        // the same offer again or removing an offer which did not provide either.
+        assert!(compare_offer(&mut ztp_fsm, 4, Some(4), TwoWay).is_empty());
+        assert!(compare_offer(&mut ztp_fsm, 2, Some(6), TwoWay).is_empty());
+        assert!(compare_offer(&mut ztp_fsm, 4, None, TwoWay).is_empty());
+
+        // Removing one of the offers providing the HAL only changes the HALS. Once all of them are
+        // gone, the HAL is lost, and so is the HAT once there is no ThreeWay offer left.
+        assert_eq!(compare_offer(&mut ztp_fsm, 2, None, TwoWay), ["BetterHAL"]);
+        assert_eq!(compare_offer(&mut ztp_fsm, 3, None, TwoWay), ["LostHAL"]);
+        assert_eq!(compare_offer(&mut ztp_fsm, 1, Some(5), TwoWay), ["LostHAT"]);
+        assert_eq!(compare_offer(&mut ztp_fsm, 1, None, TwoWay), ["LostHAL"]);
+        assert_eq!(ztp_fsm.highest_available_level, None);
+    }
+    #[test]
+    fn test_short_tic() {
+        // A ShortTic only removes expired offers, unless the node is holding down.
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+        assert!(ztp_fsm.process_external_events().is_empty());
+        assert_eq!(ztp_fsm.state, ZtpState::ComputeBestOffer);
+    }
+
+    #[test]
+    fn test_holddown() {
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        ztp_fsm.push_external_event(offer(2, 3));
+        ztp_fsm.process_external_events();
+        assert_eq!(ztp_fsm.level(), Some(4));
+
+        // Losing the HAL while a southbound adjacency is present starts the holddown timer. Until
+        // it expires, offers are stored but no level is derived from them.
+        ztp_fsm.push_external_event(ZtpEvent::NeighborOffer(Offer {
+            level: None,
+            system_id: SystemID::try_from(1).unwrap(),
+            state: LieState::TwoWay,
+            expires_at: Instant::now(),
+        }));
+        ztp_fsm.push_external_event(offer(3, 6));
+        ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+        assert!(ztp_fsm.process_external_events().is_empty());
+        assert_eq!(ztp_fsm.state, ZtpState::HoldingDown);
+        assert_eq!(ztp_fsm.level(), Some(4));
+
+        // Once the holddown expires, all offers are purged. This loses the HAL again, but without
+        // any offers there are no southbound adjacencies, so the holddown is over on the next
+        // ShortTic. The lost level is then passed on to the LIE FSMs.
+        ztp_fsm.holddown_timer.force_expire();
+        ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+        ztp_fsm.process_external_events();
+        assert!(ztp_fsm.offers.is_empty());
+        ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+        let events = ztp_fsm.process_external_events();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, LieEvent::LevelChanged(None))));
+        assert_eq!(ztp_fsm.state, ZtpState::UpdatingClients);
+    }
+
+    #[test]
+    fn test_change_configured_level() {
+        let mut ztp_fsm = ZtpStateMachine::new(None, LeafFlags::default());
+        ztp_fsm.push_external_event(offer(1, 5));
+        ztp_fsm.process_external_events();
+        assert_eq!(ztp_fsm.level(), Some(4));
+
+        // A configured level overrides the level derived from the offers...
+        ztp_fsm.push_external_event(ZtpEvent::ChangeLocalConfiguredLevel(Some(2)));
+        let events = ztp_fsm.process_external_events();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, LieEvent::LevelChanged(Some(2)))));
+        assert!(!ztp_fsm.is_level_derived_from(SystemID::try_from(1).unwrap()));
+
+        // ...and configuring the same level again does not reset the adjacencies.
+        ztp_fsm.push_external_event(ZtpEvent::ChangeLocalConfiguredLevel(Some(2)));
+        assert!(ztp_fsm.process_external_events().is_empty());
+
+        // Removing the configured level goes back to ZTP.
+        ztp_fsm.push_external_event(ZtpEvent::ChangeLocalConfiguredLevel(None));
+        let events = ztp_fsm.process_external_events();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, LieEvent::LevelChanged(Some(4)))));
+    }
+}
@@ -4,6 +4,7 @@ use clap::Parser;
 use rift_rust::{
     lie_exchange::Timer,
     network::{Network, Passivity},
+    rib::FibConfig,
     topology::TopologyDescription,
 };
 use tracing::info;
@@ -31,6 +32,10 @@ struct Args {
     /// Requires `snapshot` to be passed.
     #[arg(long, requires = "snapshot")]
     max_snapshots: Option<usize>,
+    #[arg(long)]
+    /// Write the routes of each node to `<FIB_DIR>/<node name>.batch`, in a format suitable for
+    /// `ip -batch`. Otherwise, routes are only kept in memory.
+    fib_dir: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -57,7 +62,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         topology
     };
 
-    let mut network = Network::from_desc(&topology, passivity)?;
+    let fib_config = match args.fib_dir {
+        Some(fib_dir) => FibConfig::IpBatch(fib_dir),
+        None => FibConfig::InMemory,
+    };
+
+    let mut network = Network::from_desc(&topology, passivity, &fib_config)?;
 
     let mut timer = None;
     let mut i = 0;
@@ -1,7 +1,8 @@
 use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
     error::Error,
     io,
-    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, UdpSocket},
     time::Duration,
 };
 
@@ -9,16 +10,29 @@ use rand::seq::index;
 use serde::Serialize;
 
 use crate::{
-    lie_exchange::{self, LeafFlags, LieEvent, LieState, LieStateMachine, Timer, ZtpStateMachine},
+    lie_exchange::{
+        self, LeafFlags, LieEvent, LieState, LieStateMachine, Timer, ZtpEvent, ZtpStateMachine,
+    },
     models::{
         common::{self, LinkIDType},
-        encoding::{PacketContent, ProtocolPacket},
+        encoding::{
+            LinkIDPair, NodeCapabilities, NodeNeighborsTIEElement, NodeTIEElement, PacketContent,
+            PacketHeader, PrefixAttributes, PrefixTIEElement, ProtocolPacket, TIEElement,
+            PROTOCOL_MAJOR_VERSION, PROTOCOL_MINOR_VERSION,
+        },
     },
     packet::{self, Nonce, OuterSecurityEnvelopeHeader, PacketNumber, SecretKeyStore},
+    rib::{FibConfig, FibNextHop, Rib},
     socket::{RecvPacketError, RecvPacketResult, RiftSocket},
-    tie_exchange::{LinkInfo, TieStateMachine},
-    topology::{NodeDescription, TopologyDescription},
-    wrapper::SystemID,
+    spf::{self, NextHop, Route, SpfDirection},
+    tie_exchange::{
+        self, FloodReductionConfig, LinkDirection, LinkInfo, LinkStateDatabase, RetransmitConfig,
+        TieStateMachine,
+    },
+    topology::{GlobalConstants, Interface, NodeDescription, TopologyDescription},
+    wrapper::{
+        self, LifetimeInSecs, SystemID, TIDEPacket, TIESubtype, TieDirection, TieNumber, TIEID,
+    },
 };
 
 /// Represents a network of nodes.
@@ -32,8 +46,13 @@ pub struct Network {
 impl Network {
     /// Create a network from a topology description file. The passivity determines which type of
     /// nodes are actually created. The passivity determines which types of nodes are made. Typically,
-    /// passivity is used for debugging purposes.
-    pub fn from_desc(desc: &TopologyDescription, passivity: Passivity) -> io::Result<Network> {
+    /// passivity is used for debugging purposes. The routes computed by each node are installed into
+    /// the FIB described by `fib_config`.
+    pub fn from_desc(
+        desc: &TopologyDescription,
+        passivity: Passivity,
+        fib_config: &FibConfig,
+    ) -> io::Result<Network> {
         let nodes = desc
             .get_nodes()
             .iter()
@@ -42,7 +61,7 @@ impl Network {
                 Passivity::NonPassiveOnly => !node.passive,
                 Passivity::Both => true,
             })
-            .map(|node_desc| Node::from_desc(node_desc))
+            .map(|node_desc| Node::from_desc(node_desc, &desc.constant, fib_config))
             .collect::<io::Result<_>>()?;
 
         Ok(Network {
@@ -61,6 +80,56 @@ impl Network {
         // self.nodes.shuffle(&mut rand::thread_rng());
         Ok(())
     }
+
+    /// Change the configured level of the node named `node_name` while the network is running. A
+    /// level of `None` makes the node derive its level via ZTP. A leaf-only node always has the leaf
+    /// level, so configuring any other level also clears its leaf flags. The change takes effect on
+    /// the next [Network::step].
+    pub fn set_configured_level(
+        &mut self,
+        node_name: &str,
+        level: Option<lie_exchange::Level>,
+    ) -> Result<(), UnknownNodeError> {
+        self.node_mut(node_name)?.set_configured_level(level);
+        Ok(())
+    }
+
+    /// Change the leaf flags of the node named `node_name` while the network is running. This also
+    /// changes the configured level of the node (see [LeafFlags::configured_level]). The change
+    /// takes effect on the next [Network::step].
+    pub fn set_leaf_flags(
+        &mut self,
+        node_name: &str,
+        leaf_flags: LeafFlags,
+    ) -> Result<(), LeafFlagsError> {
+        if !leaf_flags.is_valid() {
+            return Err(LeafFlagsError::Invalid(leaf_flags));
+        }
+        self.node_mut(node_name)?.set_leaf_flags(leaf_flags);
+        Ok(())
+    }
+
+    fn node_mut(&mut self, node_name: &str) -> Result<&mut Node, UnknownNodeError> {
+        self.nodes
+            .iter_mut()
+            .find(|node| node.node_info.node_name.as_deref() == Some(node_name))
+            .ok_or_else(|| UnknownNodeError(node_name.to_string()))
+    }
+}
+
+/// Returned when a node is looked up by a name which no node in the [Network] has. Note that
+/// depending on the [Passivity], a node from the topology may not be part of the network.
+#[derive(thiserror::Error, Debug)]
+#[error("no node named {0}")]
+pub struct UnknownNodeError(pub String);
+
+/// Returned by [Network::set_leaf_flags].
+#[derive(thiserror::Error, Debug)]
+pub enum LeafFlagsError {
+    #[error(transparent)]
+    UnknownNode(#[from] UnknownNodeError),
+    #[error("invalid leaf flags {0:?} (leaf_2_leaf requires leaf_only)")]
+    Invalid(LeafFlags),
 }
 
 /// A node. A node may contain one or more Links, which are the node's physical neighbors.
@@ -68,6 +137,39 @@ impl Network {
 struct Node {
     links: Vec<Link>,
     ztp_fsm: ZtpStateMachine,
+    /// The timer used for sending ShortTic events to the ZTP FSM once a second.
+    #[serde(skip)]
+    short_tic_timer: Timer,
+    /// The link state database. This is shared between all of the links on this node.
+    #[serde(skip)]
+    ls_db: LinkStateDatabase,
+    /// The prefixes configured on this node via `v4prefixes` and `v6prefixes`. These are advertised
+    /// northbound in this node's Prefix TIE.
+    #[serde(skip)]
+    configured_prefixes: BTreeMap<common::IPPrefixType, PrefixAttributes>,
+    /// If true, this node originates a default route southbound whenever it has a northbound
+    /// adjacency.
+    #[serde(skip)]
+    generate_defaults: bool,
+    /// If this node is a ToF node, the other ToF nodes it sees in the same plane as this one through
+    /// shared southbound neighbors. These are advertised in the `same_plane_tofs` of the Node TIEs.
+    #[serde(skip)]
+    same_plane_tofs: BTreeSet<SystemID>,
+    /// The routes computed by the most recent N-SPF and S-SPF, which are also installed in the FIB.
+    #[serde(skip)]
+    rib: Rib,
+    /// Determines how the flood repeaters of this node are elected.
+    #[serde(skip)]
+    flood_reduction_config: FloodReductionConfig,
+    /// The northbound neighbors the flood repeaters were last elected among.
+    #[serde(skip)]
+    flood_repeater_parents: BTreeSet<SystemID>,
+    /// The northbound neighbors currently elected as flood repeaters by this node.
+    #[serde(skip)]
+    flood_repeaters: BTreeSet<SystemID>,
+    /// If true, the next hops of each route are weighted by the bandwidth of their links.
+    #[serde(skip)]
+    bandwidth_weighted_ecmp: bool,
     #[serde(flatten)]
     node_info: NodeInfo,
 }
@@ -75,12 +177,38 @@ struct Node {
 impl Node {
     /// Create a node from a NodeDescription. This method will fail if the addresses specified in the
     /// NodeDescription cannot be bound to.
-    fn from_desc(node_desc: &NodeDescription) -> io::Result<Node> {
+    fn from_desc(
+        node_desc: &NodeDescription,
+        constants: &GlobalConstants,
+        fib_config: &FibConfig,
+    ) -> io::Result<Node> {
+        let flood_reduction_config = constants.flood_reduction_config();
         let configured_level = Option::from(node_desc.level);
+        let out_of_range = |field: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} of node {} is out of range", field, node_desc.name),
+            )
+        };
+        let pod = node_desc
+            .pod
+            .map(common::PodType::try_from)
+            .transpose()
+            .map_err(|_| out_of_range("pod"))?;
+        let fabric_id = node_desc
+            .fabric_id
+            .map(common::FabricIDType::try_from)
+            .transpose()
+            .map_err(|_| out_of_range("fabric_id"))?;
         let node_info = NodeInfo {
             node_name: Some(node_desc.name.clone()),
             configured_level,
             system_id: node_desc.system_id,
+            pod,
+            instance_name: node_desc.instance_name.clone(),
+            fabric_id,
+            flood_reduction: flood_reduction_config.enabled,
+            leaf_flags: node_desc.level.leaf_flags(),
         };
         let links = node_desc
             .interfaces
@@ -90,27 +218,123 @@ impl Node {
                 Link::from_desc(
                     local_link_id as LinkIDType,
                     node_info.clone(),
-                    link_desc.name.clone(),
-                    link_desc.lie_rx_addr(),
-                    link_desc.lie_tx_addr(),
-                    link_desc.tie_rx_addr(),
+                    link_desc,
+                    constants.retransmit_config(),
                 )
             })
             .collect::<io::Result<_>>()?;
 
+        let invalid_mask = |address: IpAddr, mask: usize| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid mask for prefix {address}/{mask}"),
+            )
+        };
+        let v4prefixes = node_desc.v4prefixes.iter().map(|prefix| {
+            let ip_prefix = prefix
+                .ip_prefix()
+                .ok_or_else(|| invalid_mask(prefix.address.into(), prefix.mask));
+            (ip_prefix, prefix.metric)
+        });
+        let v6prefixes = node_desc.v6prefixes.iter().map(|prefix| {
+            let ip_prefix = prefix
+                .ip_prefix()
+                .ok_or_else(|| invalid_mask(prefix.address.into(), prefix.mask));
+            (ip_prefix, prefix.metric)
+        });
+        let configured_prefixes = v4prefixes
+            .chain(v6prefixes)
+            .map(|(prefix, metric)| {
+                let attributes = PrefixAttributes::new(
+                    metric.get() as common::MetricType,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                Ok((prefix?, attributes))
+            })
+            .collect::<io::Result<_>>()?;
+
         Ok(Node {
             links,
-            ztp_fsm: ZtpStateMachine::new(configured_level, LeafFlags),
+            ztp_fsm: ZtpStateMachine::new(configured_level, node_info.leaf_flags),
+            short_tic_timer: Timer::new(Duration::from_secs(1)),
+            ls_db: LinkStateDatabase::new(),
+            configured_prefixes,
+            generate_defaults: node_desc.generate_defaults,
+            same_plane_tofs: BTreeSet::new(),
+            rib: Rib::new(fib_config.backend(&node_desc.name)?),
+            flood_reduction_config,
+            flood_repeater_parents: BTreeSet::new(),
+            flood_repeaters: BTreeSet::new(),
+            bandwidth_weighted_ecmp: constants.bandwidth_weighted_ecmp.unwrap_or(false),
             node_info,
         })
     }
 
+    /// Change the configured level of this node. The ZTP FSM recomputes the level of this node,
+    /// and any resulting LevelChanged is passed on to the LIE FSMs during the next step.
+    fn set_configured_level(&mut self, level: Option<lie_exchange::Level>) {
+        tracing::info!(
+            node_name = self.node_info.node_name,
+            level =? level,
+            "changing configured level"
+        );
+        if self.node_info.leaf_flags.leaf_only && level != Some(lie_exchange::LEAF_LEVEL) {
+            self.update_leaf_flags(LeafFlags::default());
+        }
+        self.node_info.configured_level = level;
+        for link in &mut self.links {
+            link.node_info.configured_level = level;
+        }
+        self.ztp_fsm
+            .push_external_event(ZtpEvent::ChangeLocalConfiguredLevel(level));
+    }
+
+    /// Change the leaf flags of this node. Besides recomputing the level in the ZTP FSM, this also
+    /// changes the hierarchy indications advertised in the LIEs and Node TIEs of this node. The
+    /// configured level is kept consistent with the flags, as described in
+    /// [LeafFlags::configured_level].
+    fn set_leaf_flags(&mut self, leaf_flags: LeafFlags) {
+        tracing::info!(
+            node_name = self.node_info.node_name,
+            leaf_flags =? leaf_flags,
+            "changing leaf flags"
+        );
+        let configured_level = self
+            .node_info
+            .leaf_flags
+            .configured_level(leaf_flags, self.node_info.configured_level);
+        self.update_leaf_flags(leaf_flags);
+        if configured_level != self.node_info.configured_level {
+            self.set_configured_level(configured_level);
+        }
+    }
+
+    /// Store the leaf flags of this node and pass them on to the ZTP FSM, without touching the
+    /// configured level.
+    fn update_leaf_flags(&mut self, leaf_flags: LeafFlags) {
+        self.node_info.leaf_flags = leaf_flags;
+        for link in &mut self.links {
+            link.node_info.leaf_flags = leaf_flags;
+        }
+        self.ztp_fsm
+            .push_external_event(ZtpEvent::ChangeLocalHierarchyIndications(leaf_flags));
+    }
+
     /// Run the node for one step.
     fn step(&mut self, key: &SecretKeyStore) -> Result<(), Box<dyn Error>> {
         let _span =
             tracing::debug_span!("node_step", node_name = self.node_info.node_name,).entered();
 
         // Run the ZTP FSM
+        if self.short_tic_timer.is_expired() {
+            self.ztp_fsm.push_external_event(ZtpEvent::ShortTic);
+            self.short_tic_timer.start();
+        }
         let lie_events = self.ztp_fsm.process_external_events();
 
         // Add any LIE events returned by the ZTP to the LIE FSMs
@@ -122,10 +346,324 @@ impl Node {
 
         // Run each LIE FSM
         for link in &mut self.links {
-            link.step(key, &mut self.ztp_fsm)?;
+            link.step(key, &mut self.ztp_fsm, &mut self.ls_db)?;
+        }
+        self.update_flood_reduced_originators();
+
+        self.ls_db.age();
+        self.originate_node_ties();
+        self.originate_prefix_ties();
+        self.originate_default_route();
+
+        // Flood any TIEs which were newly added to the LSDB out of every adjacency, except those to
+        // the neighbor the TIE was received from. The TIE was acknowledged to that neighbor while
+        // stepping the links, so its ACK queue no longer holds the TIE to prevent sending it back.
+        for (tie, received_from) in self.ls_db.take_new_ties() {
+            for link in &mut self.links {
+                let Some(link_info) = link.link_info() else {
+                    continue;
+                };
+                if received_from != Some(link_info.neighbor.system_id) {
+                    link.tie_fsm
+                        .try_to_transmit_tie(&link_info, &self.ls_db, tie);
+                }
+            }
+        }
+
+        let parents = self
+            .links
+            .iter()
+            .filter_map(|link| link.link_info())
+            .filter(|link_info| link_info.direction() == LinkDirection::North)
+            .map(|link_info| link_info.neighbor.system_id)
+            .collect::<BTreeSet<_>>();
+        let ls_db_changed = self.ls_db.take_changed();
+        if ls_db_changed {
+            self.compute_routes();
+        }
+        if ls_db_changed || parents != self.flood_repeater_parents {
+            self.elect_flood_repeaters(parents);
         }
         Ok(())
     }
+
+    /// Run the flood repeater election over the northbound neighbors of this node (see
+    /// [tie_exchange::flood_repeaters]). If the flood repeaters changed, every LIE FSM is told so
+    /// that the neighbors learn whether they are flood repeaters.
+    fn elect_flood_repeaters(&mut self, parents: BTreeSet<SystemID>) {
+        let flood_repeaters = tie_exchange::flood_repeaters(
+            &self.ls_db,
+            self.node_info.system_id,
+            &parents,
+            self.flood_reduction_config,
+        );
+        self.flood_repeater_parents = parents;
+        if flood_repeaters == self.flood_repeaters {
+            return;
+        }
+
+        tracing::info!(flood_repeaters =? flood_repeaters, "flood leaders changed");
+        for link in &mut self.links {
+            link.lie_fsm
+                .push_external_event(LieEvent::FloodLeadersChanged(flood_repeaters.clone()));
+        }
+        self.flood_repeaters = flood_repeaters;
+    }
+
+    /// Tell each TIE FSM which southbound neighbors did not elect this node as a flood repeater,
+    /// so that North TIEs originated by those neighbors are not reflooded northbound.
+    fn update_flood_reduced_originators(&mut self) {
+        let originators = self
+            .links
+            .iter()
+            .filter(|link| !link.lie_fsm.is_flood_repeater())
+            .filter_map(|link| link.link_info())
+            .filter(|link_info| link_info.direction() == LinkDirection::South)
+            .map(|link_info| link_info.neighbor.system_id)
+            .collect::<BTreeSet<_>>();
+        for link in &mut self.links {
+            link.tie_fsm
+                .set_flood_reduced_originators(originators.clone());
+        }
+    }
+
+    /// Recompute the routes of this node by running both the N-SPF and the S-SPF. Routes found by
+    /// the S-SPF are preferred over routes to the same prefix found by the N-SPF.
+    fn compute_routes(&mut self) {
+        let system_id = self.node_info.system_id;
+        let mut routes = spf::compute_routes(&self.ls_db, system_id, SpfDirection::North);
+        let south_routes = spf::compute_routes(&self.ls_db, system_id, SpfDirection::South);
+        self.originate_positive_disaggregation(&south_routes);
+
+        // ToF nodes determine which prefixes are unreachable in their plane, while every other node
+        // punches holes in its default route based on what its northbound neighbors disaggregated.
+        let negative_prefixes = if self.ztp_fsm.level() == Some(wrapper::TOP_OF_FABRIC_LEVEL) {
+            self.same_plane_tofs = spf::same_plane_tofs(&self.ls_db, system_id);
+            spf::tof_negative_disaggregation(&self.ls_db, system_id, &self.same_plane_tofs)
+        } else {
+            spf::apply_negative_disaggregation(&self.ls_db, system_id, &mut routes)
+        };
+        self.originate_negative_disaggregation(negative_prefixes);
+        routes.extend(south_routes);
+
+        if &routes == self.rib.routes() {
+            return;
+        }
+        tracing::info!(routes =? routes, "routes changed");
+
+        let links = &self.links;
+        let bandwidth_weighted_ecmp = self.bandwidth_weighted_ecmp;
+        let resolve = |next_hop: &NextHop| {
+            links.iter().find_map(|link| {
+                let link_info = link.link_info()?;
+                let is_next_hop = link.link_socket.local_link_id == next_hop.link_id
+                    && link_info.neighbor.system_id == next_hop.system_id;
+                is_next_hop.then(|| FibNextHop {
+                    address: link_info.neighbor.address,
+                    interface: link.link_socket.name.clone(),
+                    weight: next_hop.weight.filter(|_| bandwidth_weighted_ecmp),
+                })
+            })
+        };
+        if let Err(err) = self.rib.update(routes, resolve) {
+            tracing::error!(err =? err, "Error while updating FIB");
+        }
+    }
+
+    /// Originate the North and South Node TIEs for this node, which describe every neighbor the
+    /// node is currently in ThreeWay with. Parallel links to the same neighbor are combined into a
+    /// single neighbor entry. A new version of the TIEs (with a bumped sequence number) is only
+    /// originated if the contents changed, which happens whenever a link enters or leaves ThreeWay.
+    fn originate_node_ties(&mut self) {
+        let Some(level) = self.ztp_fsm.level() else {
+            return;
+        };
+
+        let mut neighbors: BTreeMap<common::SystemIDType, NodeNeighborsTIEElement> =
+            BTreeMap::new();
+        for link in &self.links {
+            if let Some(link_info) = link.link_info() {
+                let neighbor = neighbors
+                    .entry(link_info.neighbor.system_id.get())
+                    .or_insert_with(|| NodeNeighborsTIEElement {
+                        level: link_info.neighbor.level as common::LevelType,
+                        cost: Some(link.metric),
+                        link_ids: Some(BTreeSet::new()),
+                        bandwidth: Some(0),
+                    });
+                let link_id = LinkIDPair::new(
+                    link.link_socket.local_link_id,
+                    link_info.neighbor.local_link_id,
+                    None,
+                    link.link_socket.name.clone(),
+                    None,
+                    None,
+                    None,
+                );
+                neighbor.link_ids.as_mut().unwrap().insert(link_id);
+                // Parallel links add up their bandwidth, and the cheapest one determines the cost.
+                let bandwidth = neighbor.bandwidth.as_mut().unwrap();
+                *bandwidth = bandwidth.saturating_add(link.link_socket.bandwidth);
+                let cost = neighbor.cost.as_mut().unwrap();
+                *cost = (*cost).min(link.metric);
+            }
+        }
+
+        // Only ToF nodes advertise the other ToF nodes in their plane.
+        let same_plane_tofs = (level == wrapper::TOP_OF_FABRIC_LEVEL).then(|| {
+            self.same_plane_tofs
+                .iter()
+                .map(SystemID::get)
+                .collect::<BTreeSet<_>>()
+        });
+
+        let element = NodeTIEElement {
+            capabilities: self.node_info.node_capabilities(),
+            name: self.node_info.node_name.clone(),
+            same_plane_tofs,
+            ..wrapper::node_element(level as common::LevelType, neighbors)
+        };
+
+        for direction in [TieDirection::North, TieDirection::South] {
+            let tie_id = TIEID {
+                direction,
+                originator: self.node_info.system_id,
+                tie_type: TIESubtype::Node,
+                tie_nr: TieNumber::FIRST,
+            };
+            if self
+                .ls_db
+                .originate(tie_id, TIEElement::Node(element.clone()))
+            {
+                tracing::info!(direction =? direction, "originated new node TIE");
+            }
+        }
+    }
+
+    /// Originate the North Prefix TIE for this node, which contains the configured prefixes along
+    /// with the subnets of any interfaces marked `advertise_subnet`.
+    fn originate_prefix_ties(&mut self) {
+        let mut prefixes = self.configured_prefixes.clone();
+        for link in &self.links {
+            if let Some(subnet) = &link.subnet {
+                let attributes = PrefixAttributes::new(
+                    common::DEFAULT_DISTANCE,
+                    None,
+                    None,
+                    None,
+                    true,
+                    link.link_socket.local_link_id,
+                    None,
+                );
+                prefixes.insert(subnet.clone(), attributes);
+            }
+        }
+
+        let tie_id = TIEID {
+            direction: TieDirection::North,
+            originator: self.node_info.system_id,
+            tie_type: TIESubtype::Prefix,
+            tie_nr: TieNumber::FIRST,
+        };
+        let element = TIEElement::Prefixes(PrefixTIEElement::new(prefixes));
+        if self.ls_db.originate(tie_id, element) {
+            tracing::info!("originated new prefix TIE");
+        }
+    }
+
+    /// Originate the Positive Disaggregation Prefix TIE for this node, which contains the prefixes
+    /// this node can reach southbound but some other node on the same level can't (see
+    /// [spf::positive_disaggregation]). If nothing needs to be disaggregated but something was
+    /// previously, the TIE is emptied.
+    fn originate_positive_disaggregation(
+        &mut self,
+        south_routes: &BTreeMap<common::IPPrefixType, Route>,
+    ) {
+        let tie_id = TIEID {
+            direction: TieDirection::South,
+            originator: self.node_info.system_id,
+            tie_type: TIESubtype::PositiveDisaggregationPrefix,
+            tie_nr: TieNumber::FIRST,
+        };
+
+        let prefixes =
+            spf::positive_disaggregation(&self.ls_db, self.node_info.system_id, south_routes);
+        if prefixes.is_empty() && self.ls_db.header_with_lifetime(&tie_id).is_none() {
+            return;
+        }
+
+        let num_prefixes = prefixes.len();
+        let element = TIEElement::PositiveDisaggregationPrefixes(PrefixTIEElement::new(prefixes));
+        if self.ls_db.originate(tie_id, element) {
+            tracing::info!(num_prefixes, "originated new positive disaggregation TIE");
+        }
+    }
+
+    /// Originate the Negative Disaggregation Prefix TIE for this node, which contains the given
+    /// prefixes. If there are no prefixes but there were previously, the TIE is emptied.
+    fn originate_negative_disaggregation(
+        &mut self,
+        prefixes: BTreeMap<common::IPPrefixType, PrefixAttributes>,
+    ) {
+        let tie_id = TIEID {
+            direction: TieDirection::South,
+            originator: self.node_info.system_id,
+            tie_type: TIESubtype::NegativeDisaggregationPrefix,
+            tie_nr: TieNumber::FIRST,
+        };
+        if prefixes.is_empty() && self.ls_db.header_with_lifetime(&tie_id).is_none() {
+            return;
+        }
+
+        let num_prefixes = prefixes.len();
+        let element = TIEElement::NegativeDisaggregationPrefixes(PrefixTIEElement::new(prefixes));
+        if self.ls_db.originate(tie_id, element) {
+            tracing::info!(num_prefixes, "originated new negative disaggregation TIE");
+        }
+    }
+
+    /// Originate the South Prefix TIE for this node, which contains the IPv4 and IPv6 default
+    /// routes if `generate_defaults` is set and the node has at least one northbound adjacency.
+    /// If the default routes should not be advertised but were previously, the TIE is emptied.
+    fn originate_default_route(&mut self) {
+        let tie_id = TIEID {
+            direction: TieDirection::South,
+            originator: self.node_info.system_id,
+            tie_type: TIESubtype::Prefix,
+            tie_nr: TieNumber::FIRST,
+        };
+
+        let has_northbound = self.links.iter().any(|link| {
+            link.link_info()
+                .is_some_and(|link_info| link_info.direction() == LinkDirection::North)
+        });
+
+        let mut prefixes = BTreeMap::new();
+        if self.generate_defaults && has_northbound {
+            for address in [
+                IpAddr::from(Ipv4Addr::UNSPECIFIED),
+                Ipv6Addr::UNSPECIFIED.into(),
+            ] {
+                let attributes = PrefixAttributes::new(
+                    common::DEFAULT_DISTANCE,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                prefixes.insert(wrapper::ip_prefix(address, 0), attributes);
+            }
+        } else if self.ls_db.header_with_lifetime(&tie_id).is_none() {
+            return;
+        }
+
+        let element = TIEElement::Prefixes(PrefixTIEElement::new(prefixes));
+        if self.ls_db.originate(tie_id, element) {
+            tracing::info!(has_northbound, "originated new default route TIE");
+        }
+    }
 }
 
 /// A Link represents a physical connection between two nodes. Note that, even if two nodes are
@@ -147,57 +685,130 @@ struct Link {
     #[serde(skip)]
     // The timer used for sending TimerTick events periodically.
     last_timer_tick: Timer,
-    /// The timer used for doing TIDE generation and TIE sending periodically.
+    /// The timer used for doing TIDE generation and TIE request resending periodically.
     #[serde(skip)]
     tie_timer: Timer,
+    /// TIDEs which have been generated but not yet sent. TIDEs are sent one per step so that they
+    /// are spaced out, as recommended by the spec.
+    #[serde(skip)]
+    tide_queue: VecDeque<TIDEPacket>,
+    /// The subnet of this link, if the link is configured to advertise it.
+    #[serde(skip)]
+    subnet: Option<common::IPPrefixType>,
+    /// The cost of this link, as advertised in the Node TIEs.
+    #[serde(skip)]
+    metric: common::MetricType,
 }
 
 impl Link {
-    /// Create a node from a NodeDescription. This method will fail if `lie_rx_addr` or `lie_tx_addr`
-    /// cannot be bound to.
+    /// Create a link from an Interface. This method will fail if the LIE or TIE addresses of the
+    /// interface cannot be bound to.
     fn from_desc(
         local_link_id: LinkIDType,
         node_info: NodeInfo,
-        link_name: String,
-        lie_rx_addr: SocketAddr,
-        lie_tx_addr: SocketAddr,
-        tie_rx_addr: SocketAddr,
+        link_desc: &Interface,
+        retransmit_config: RetransmitConfig,
     ) -> io::Result<Link> {
+        // The MTU is advertised in LIEs, so it must fit in an MTUSizeType.
+        let mtu = link_desc.mtu.unwrap_or(common::DEFAULT_MTU_SIZE as usize);
+        if mtu == 0 || common::MTUSizeType::try_from(mtu).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("MTU of interface {} is out of range", link_desc.name),
+            ));
+        }
+        let mut link_socket = LinkSocket::new(
+            link_desc.name.clone(),
+            local_link_id,
+            link_desc.lie_rx_addr(),
+            link_desc.lie_tx_addr(),
+            link_desc.tie_rx_addr(),
+            mtu,
+        )?;
+        link_socket.label = link_desc
+            .label
+            .map(common::LabelType::try_from)
+            .transpose()
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("label of interface {} is out of range", link_desc.name),
+                )
+            })?;
+        link_socket.bandwidth = link_desc
+            .bandwidth
+            .map_or(Ok(common::DEFAULT_BANDWIDTH), |bandwidth| {
+                common::BandwithInMegaBitsType::try_from(bandwidth)
+            })
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("bandwidth of interface {} is out of range", link_desc.name),
+                )
+            })?;
+        let metric = link_desc
+            .metric
+            .map_or(Ok(common::DEFAULT_DISTANCE), |metric| {
+                common::MetricType::try_from(metric.get())
+            })
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("metric of interface {} is out of range", link_desc.name),
+                )
+            })?;
+        let subnet = match &link_desc.subnet {
+            Some(subnet) if link_desc.advertise_subnet => {
+                let Some(prefix) = subnet.ip_prefix() else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid subnet mask for interface {}", link_desc.name),
+                    ));
+                };
+                Some(prefix)
+            }
+            None if link_desc.advertise_subnet => {
+                tracing::warn!(
+                    interface = link_desc.name,
+                    "advertise_subnet is set, but the interface has no subnet"
+                );
+                None
+            }
+            _ => None,
+        };
+
         Ok(Link {
-            link_socket: LinkSocket::new(
-                link_name,
-                local_link_id,
-                lie_rx_addr,
-                lie_tx_addr,
-                tie_rx_addr,
-                common::DEFAULT_MTU_SIZE as usize,
-            )?,
+            link_socket,
             lie_fsm: LieStateMachine::new(node_info.configured_level),
-            tie_fsm: TieStateMachine::new(),
+            tie_fsm: TieStateMachine::new(retransmit_config),
             node_info,
             last_timer_tick: Timer::new(Duration::from_secs(1)),
             tie_timer: Timer::new(Duration::from_secs(1)),
+            tide_queue: VecDeque::new(),
+            subnet,
+            metric,
         })
     }
 
+    /// Returns Some if the Link is currently in ThreeWay along with some information about the Link.
+    fn link_info(&self) -> Option<LinkInfo> {
+        if self.lie_fsm.lie_state == LieState::ThreeWay {
+            Some(LinkInfo {
+                local_system_id: self.node_info.system_id,
+                local_level: self.lie_fsm.level().unwrap(),
+                neighbor: self.lie_fsm.neighbor.clone().unwrap(),
+            })
+        } else {
+            None
+        }
+    }
+
     pub fn step(
         &mut self,
         keys: &SecretKeyStore,
         ztp_fsm: &mut ZtpStateMachine,
+        ls_db: &mut LinkStateDatabase,
     ) -> Result<(), Box<dyn Error>> {
-        // Returns Some if the Link is currently in ThreeWay along with some information about the Link.
-        fn is_threeway(link: &Link) -> Option<LinkInfo> {
-            if link.lie_fsm.lie_state == LieState::ThreeWay {
-                Some(crate::tie_exchange::LinkInfo {
-                    local_system_id: link.node_info.system_id,
-                    local_level: link.lie_fsm.level().unwrap(),
-                    neighbor: link.lie_fsm.neighbor.clone().unwrap(),
-                })
-            } else {
-                None
-            }
-        }
-
         let _span = tracing::debug_span!(
             "link_step",
             node_name = self.node_info.node_name,
@@ -206,7 +817,7 @@ impl Link {
         .entered();
 
         let packets = self.link_socket.recv_packets(keys)?;
-        for (packet, address) in packets {
+        for (packet, address, remaining_lifetime) in packets {
             match packet.content {
                 PacketContent::Lie(content) => self.lie_fsm.push_external_event(LieEvent::LieRcvd(
                     address.ip(),
@@ -215,7 +826,7 @@ impl Link {
                 )),
                 PacketContent::Tide(tide) => {
                     let tide = &tide.into();
-                    if let Some(link_info) = is_threeway(self) {
+                    if let Some(link_info) = self.link_info() {
                         let from_northbound = match packet.header.level {
                             Some(level) => {
                                 Some((level + 1) as lie_exchange::Level) == self.lie_fsm.level()
@@ -224,20 +835,24 @@ impl Link {
                         };
 
                         if let Err(err) =
-                            self.tie_fsm.process_tide(&link_info, from_northbound, tide)
+                            self.tie_fsm
+                                .process_tide(&link_info, ls_db, from_northbound, tide)
                         {
                             tracing::error!(tide =? tide, err =? err, "Error while processing TIDE");
                         }
                     }
                 }
                 PacketContent::Tire(tire) => {
-                    if let Some(link_info) = is_threeway(self) {
-                        self.tie_fsm.process_tire(&link_info, &tire.into())
+                    if let Some(link_info) = self.link_info() {
+                        self.tie_fsm.process_tire(&link_info, ls_db, &tire.into())
                     }
                 }
                 PacketContent::Tie(tie) => {
-                    if let Some(link_info) = is_threeway(self) {
-                        self.tie_fsm.process_tie(&link_info, &tie.into())
+                    if let Some(link_info) = self.link_info() {
+                        let remaining_lifetime = remaining_lifetime
+                            .unwrap_or(common::DEFAULT_LIFETIME as LifetimeInSecs);
+                        self.tie_fsm
+                            .process_tie(&link_info, ls_db, &tie.into(), remaining_lifetime)
                     }
                 }
             }
@@ -251,19 +866,76 @@ impl Link {
         self.lie_fsm
             .process_external_events(&mut self.link_socket, &self.node_info, ztp_fsm)?;
 
-        if self.lie_fsm.lie_state == LieState::ThreeWay {
-            if self.tie_timer.is_expired() {
+        if let Some(link_info) = self.link_info() {
+            let flood_addr = SocketAddr::new(
+                link_info.neighbor.address,
+                link_info.neighbor.flood_port as u16,
+            );
+
+            let tie_timer_expired = self.tie_timer.is_expired();
+            if tie_timer_expired {
                 self.tie_timer.start();
-                self.tie_fsm
-                    .generate_tide(self.link_socket.tirdes_per_pkt());
-                self.tie_fsm.send_ties();
+                // Only generate new TIDEs once all of the previous ones have been sent.
+                if self.tide_queue.is_empty() {
+                    let tides = self.tie_fsm.generate_tide(
+                        &link_info,
+                        ls_db,
+                        self.link_socket.tirdes_per_pkt(),
+                    );
+                    self.tide_queue.extend(tides);
+                }
             }
 
-            self.tie_fsm.generate_tire();
+            if let Some(tide) = self.tide_queue.pop_front() {
+                let packet = self.flood_packet(&link_info, PacketContent::Tide(tide.into()));
+                if let Err(err) = self.link_socket.send_flood_packet(&packet, flood_addr) {
+                    tracing::error!(err =? err, "Error while sending TIDE");
+                }
+            }
+
+            for (tie, remaining_lifetime) in self.tie_fsm.send_ties(ls_db) {
+                let packet = self.flood_packet(&link_info, PacketContent::Tie(tie.into()));
+                if let Err(err) = self
+                    .link_socket
+                    .send_tie(&packet, flood_addr, remaining_lifetime)
+                {
+                    tracing::error!(err =? err, "Error while sending TIE");
+                }
+            }
+
+            // TIREs are sent as fast as feasible, but only if there is something in them. Pending
+            // requests are only included periodically, so that they are not sent every step.
+            let tires = self.tie_fsm.generate_tire(
+                ls_db,
+                self.link_socket.tirdes_per_pkt(),
+                tie_timer_expired,
+            );
+            for tire in tires {
+                let packet = self.flood_packet(&link_info, PacketContent::Tire(tire.into()));
+                if let Err(err) = self.link_socket.send_flood_packet(&packet, flood_addr) {
+                    tracing::error!(err =? err, "Error while sending TIRE");
+                }
+            }
+        } else {
+            self.tide_queue.clear();
         }
 
         Ok(())
     }
+
+    /// Wrap the given packet content in a ProtocolPacket with a header suitable for sending over
+    /// this link.
+    fn flood_packet(&self, link_info: &LinkInfo, content: PacketContent) -> ProtocolPacket {
+        ProtocolPacket {
+            header: PacketHeader {
+                major_version: PROTOCOL_MAJOR_VERSION,
+                minor_version: PROTOCOL_MINOR_VERSION,
+                sender: self.node_info.system_id.into(),
+                level: Some(link_info.local_level as common::LevelType),
+            },
+            content,
+        }
+    }
 }
 
 /// A wrapper struct for the LIE send and recv sockets. This struct also contains the state required
@@ -274,13 +946,19 @@ pub struct LinkSocket {
     lie_rx_socket: Box<dyn RiftSocket>,
     /// The socket that this link will send LIE packets to.
     lie_tx_socket: Box<dyn RiftSocket>,
-    /// The port that this link will receive TIE packets from.
-    /// TODO: This should probably become a RiftSocket eventually.
+    /// The socket that this link will receive TIE packets from.
     tie_rx_socket: Box<dyn RiftSocket>,
+    /// The socket that this link will send TIE packets to. This socket is not connected, since
+    /// TIEs are sent to the address and flood port advertised by the neighbor.
+    tie_tx_socket: Box<dyn RiftSocket>,
     /// The name of this link, typically specified by the topology description file
     pub name: String,
     /// The maximum transmissible unit size.
     pub mtu: usize,
+    /// The label advertised to the neighbor in LIEs, if one is configured for this link.
+    pub label: Option<common::LabelType>,
+    /// The bandwidth of this link, which is advertised in LIEs and Node TIEs.
+    pub bandwidth: common::BandwithInMegaBitsType,
     /// The local link ID. This value must be unique across all the links on a particular node, but
     /// does not need to be unique across nodes.
     pub local_link_id: LinkIDType,
@@ -304,9 +982,9 @@ pub struct LinkSocket {
 }
 
 impl LinkSocket {
-    /// Create a new LinkSocket. This function will fail if `lie_rx_addr` cannot be bound to or if
-    /// `lie_tx_addr` cannot be connected to. Additionally, this function fails if `lie_rx_addr` is
-    /// a multicast address and cannot be joined.
+    /// Create a new LinkSocket. This function will fail if `lie_rx_addr` or `tie_rx_addr` cannot be
+    /// bound to or if `lie_tx_addr` cannot be connected to. Additionally, this function fails if
+    /// `lie_rx_addr` or `tie_rx_addr` is a multicast address and cannot be joined.
     fn new(
         name: String,
         local_link_id: LinkIDType,
@@ -321,21 +999,7 @@ impl LinkSocket {
         let lie_rx_socket = UdpSocket::bind(lie_rx_addr)?;
         tracing::info!(recv_addr =% lie_rx_addr, send_addr =% lie_tx_addr, "recv socket bound");
 
-        // If the receive address is multicast, then we need to join the mutlicast group. We leave
-        // the interface unspecified here, since we don't care about which particular interface we
-        // receive messages on (we want all of them).
-        if lie_rx_addr.ip().is_multicast() {
-            match &lie_rx_addr.ip() {
-                IpAddr::V4(multiaddr) => {
-                    lie_rx_socket.join_multicast_v4(multiaddr, &Ipv4Addr::UNSPECIFIED)?
-                }
-                IpAddr::V6(multiaddr) => lie_rx_socket.join_multicast_v6(multiaddr, 0)?,
-            }
-            tracing::info!(
-                address =% lie_rx_addr,
-                "recv socket joined multicast group",
-            );
-        }
+        join_multicast(&lie_rx_socket, lie_rx_addr)?;
 
         // Set the receving socket to non-blocking.
         lie_rx_socket.set_nonblocking(true)?;
@@ -349,8 +1013,10 @@ impl LinkSocket {
         lie_tx_socket.connect(lie_tx_addr)?;
 
         let tie_rx_socket = UdpSocket::bind(tie_rx_addr)?;
+        join_multicast(&tie_rx_socket, tie_rx_addr)?;
         tie_rx_socket.set_nonblocking(true)?;
-        // TODO: does the TIE rx socket need to be on multicast?
+
+        let tie_tx_socket = UdpSocket::bind(unspecified)?;
 
         Ok(LinkSocket {
             name,
@@ -358,7 +1024,10 @@ impl LinkSocket {
             lie_rx_socket: Box::new(lie_rx_socket),
             lie_tx_socket: Box::new(lie_tx_socket),
             tie_rx_socket: Box::new(tie_rx_socket),
+            tie_tx_socket: Box::new(tie_tx_socket),
             mtu,
+            label: None,
+            bandwidth: common::DEFAULT_BANDWIDTH,
             packet_number: PacketNumber::from(1),
             weak_nonce_local: Nonce::from(1),
             weak_nonce_remote: Nonce::Invalid,
@@ -368,7 +1037,9 @@ impl LinkSocket {
     pub fn recv_packets(
         &mut self,
         keys: &SecretKeyStore,
-    ) -> Result<Vec<(ProtocolPacket, SocketAddr)>, RecvPacketError> {
+    ) -> Result<Vec<(ProtocolPacket, SocketAddr, Option<LifetimeInSecs>)>, RecvPacketError> {
+        // Every RIFT packet fits in the MTU (minus the IP and UDP headers), so anything which fills
+        // the entire buffer must have been truncated.
         let mut buf = vec![0; self.mtu];
 
         let mut packets = vec![];
@@ -383,7 +1054,7 @@ impl LinkSocket {
         } = lie_result
         {
             self.weak_nonce_remote = outer_header.weak_nonce_local;
-            packets.push((packet, address));
+            packets.push((packet, address, outer_header.remaining_tie_lifetime));
         } else if let RecvPacketResult::Err(err) = lie_result {
             return Err(err);
         }
@@ -396,7 +1067,7 @@ impl LinkSocket {
         } = tie_result
         {
             self.weak_nonce_remote = outer_header.weak_nonce_local;
-            packets.push((packet, address));
+            packets.push((packet, address, outer_header.remaining_tie_lifetime));
         } else if let RecvPacketResult::Err(err) = tie_result {
             return Err(err);
         }
@@ -419,6 +1090,64 @@ impl LinkSocket {
         result
     }
 
+    /// Send a packet (typically a TIDE or TIRE) to `flood_addr`, which is the neighbor's address
+    /// along with the flood port it advertised.
+    pub fn send_flood_packet(
+        &mut self,
+        packet: &ProtocolPacket,
+        flood_addr: SocketAddr,
+    ) -> io::Result<usize> {
+        self.send_to_flood_port(flood_addr, |outer_header| {
+            packet::serialize(outer_header, packet)
+        })
+    }
+
+    /// Send a packet containing a TIE to `flood_addr`, which is the neighbor's address along with
+    /// the flood port it advertised. The TIE is sent with a TIE Origin security envelope carrying
+    /// the given remaining lifetime.
+    pub fn send_tie(
+        &mut self,
+        packet: &ProtocolPacket,
+        flood_addr: SocketAddr,
+        remaining_lifetime: LifetimeInSecs,
+    ) -> io::Result<usize> {
+        self.send_to_flood_port(flood_addr, |outer_header| {
+            packet::serialize_tie(outer_header, packet, remaining_lifetime)
+        })
+    }
+
+    /// Serialize a packet using `serialize` and send it to `flood_addr`. Packets which do not fit
+    /// in the MTU of this link are not sent, since the neighbor would drop them anyways.
+    fn send_to_flood_port(
+        &mut self,
+        flood_addr: SocketAddr,
+        serialize: impl FnOnce(OuterSecurityEnvelopeHeader) -> Vec<u8>,
+    ) -> io::Result<usize> {
+        let outer_header = OuterSecurityEnvelopeHeader::new(
+            self.weak_nonce_local,
+            self.weak_nonce_remote,
+            self.packet_number,
+        );
+        let buf = serialize(outer_header);
+        let max_len = packet::max_packet_len(self.mtu);
+        if buf.len() > max_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "packet of {} bytes exceeds the MTU of interface {} ({} bytes available)",
+                    buf.len(),
+                    self.name,
+                    max_len
+                ),
+            ));
+        }
+        let result = self.tie_tx_socket.send_to(&buf, flood_addr);
+
+        self.packet_number = self.packet_number + 1;
+
+        result
+    }
+
     pub fn flood_port(&self) -> u16 {
         self.tie_rx_socket.get().local_addr().unwrap().port()
     }
@@ -427,8 +1156,22 @@ impl LinkSocket {
     /// implementation to limit the amount of TIE headers per TIDE so the sent TIDE PDU does not
     /// exceed interface MTU
     fn tirdes_per_pkt(&self) -> usize {
-        5 // TODO: i made up this number
+        packet::tirdes_per_pkt(self.mtu)
+    }
+}
+
+/// If `addr` is multicast, join the multicast group on `socket`. We leave the interface unspecified
+/// here, since we don't care about which particular interface we receive messages on (we want all
+/// of them).
+fn join_multicast(socket: &UdpSocket, addr: SocketAddr) -> io::Result<()> {
+    if addr.ip().is_multicast() {
+        match &addr.ip() {
+            IpAddr::V4(multiaddr) => socket.join_multicast_v4(multiaddr, &Ipv4Addr::UNSPECIFIED)?,
+            IpAddr::V6(multiaddr) => socket.join_multicast_v6(multiaddr, 0)?,
+        }
+        tracing::info!(address =% addr, "recv socket joined multicast group");
     }
+    Ok(())
 }
 
 /// A convience struct for keep track of node specific information.
@@ -440,6 +1183,37 @@ pub struct NodeInfo {
     pub configured_level: Option<lie_exchange::Level>,
     /// The system ID of this node. Note that this is unique across all of the nodes.
     pub system_id: SystemID,
+    /// The PoD this node belongs to, if configured.
+    pub pod: Option<common::PodType>,
+    /// The name of the RIFT instance, in case multiple instances run on the same interfaces.
+    pub instance_name: Option<String>,
+    /// The ID of the auto-EVPN fabric this node belongs to, if configured.
+    pub fabric_id: Option<common::FabricIDType>,
+    /// Whether this node supports (and performs) flooding reduction.
+    pub flood_reduction: bool,
+    /// The leaf flags of this node, which are advertised as hierarchy indications in the node
+    /// capabilities.
+    pub leaf_flags: LeafFlags,
+}
+
+impl NodeInfo {
+    /// Whether this node supports the leaf-2-leaf procedures (4.3.9), and so may form East-West
+    /// adjacencies with other leaves which also support them.
+    pub fn supports_leaf_2_leaf(&self) -> bool {
+        self.leaf_flags.hierarchy_indications()
+            == Some(common::HierarchyIndications::LEAF_ONLY_AND_LEAF_2_LEAF_PROCEDURES)
+    }
+
+    /// The capabilities advertised by this node in its LIEs and Node TIEs.
+    pub fn node_capabilities(&self) -> NodeCapabilities {
+        NodeCapabilities {
+            protocol_minor_version: PROTOCOL_MINOR_VERSION,
+            flood_reduction: Some(self.flood_reduction),
+            hierarchy_indications: self.leaf_flags.hierarchy_indications(),
+            auto_evpn_support: None,
+            auto_flood_reflection_support: None,
+        }
+    }
 }
 
 /// Which nodes to create from topology description files.
@@ -451,3 +1225,255 @@ pub enum Passivity {
     /// Create both passive and non-passive nodes.
     Both,
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        io,
+        net::{Ipv4Addr, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        lie_exchange::{LeafFlags, LieState, LEAF_LEVEL},
+        models::{
+            common,
+            encoding::{
+                self, PacketContent, PacketHeader, PrefixAttributes, PrefixTIEElement,
+                ProtocolPacket, TIEElement, TIEHeader, TIEPacket, PROTOCOL_MAJOR_VERSION,
+                PROTOCOL_MINOR_VERSION,
+            },
+        },
+        packet::{self, OuterSecurityEnvelopeHeader, SecretKeyStore},
+        rib::FibConfig,
+        tie_exchange::DBTie,
+        topology::TopologyDescription,
+        wrapper::{self, SystemID, TIESubtype, TieDirection, TieNumber, TIEID},
+    };
+
+    use super::{LinkSocket, Network, Passivity};
+
+    /// Node 1 is connected to the passive node 2 over an interface with a configured bandwidth and
+    /// metric. The test plays the part of node 2, which receives LIEs on port 20182.
+    const TWO_NODES: &str = "
+shards:
+  - id: 0
+    nodes:
+      - name: node1
+        level: 1
+        systemid: 1
+        rx_lie_mcast_address: 127.0.0.1
+        interfaces:
+          - name: if1
+            rx_lie_port: 20181
+            tx_lie_port: 20182
+            rx_tie_port: 20183
+            bandwidth: 400
+            metric: 3
+      - name: node2
+        passive: true
+        level: 0
+        systemid: 2
+        rx_lie_mcast_address: 127.0.0.1
+        interfaces:
+          - name: if1
+            rx_lie_port: 20182
+            tx_lie_port: 20181
+            rx_tie_port: 20184
+";
+
+    #[test]
+    fn test_advertise_bandwidth_and_metric() {
+        let mut topology: TopologyDescription = serde_yaml::from_str(TWO_NODES).unwrap();
+        topology.finalize();
+        let mut network =
+            Network::from_desc(&topology, Passivity::NonPassiveOnly, &FibConfig::InMemory).unwrap();
+        let node2 = UdpSocket::bind("127.0.0.1:20182").unwrap();
+        node2
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let keys = SecretKeyStore::new(HashMap::new());
+
+        // The configured bandwidth is advertised in the LIEs.
+        let mut buf = [0; 4096];
+        let lie = (0..500)
+            .find_map(|_| {
+                network.step().unwrap();
+                let len = node2.recv(&mut buf).ok()?;
+                let (_, _, packet) = packet::parse_and_validate(&buf[..len], &keys).unwrap();
+                match packet.content {
+                    PacketContent::Lie(lie) => Some(lie),
+                    _ => None,
+                }
+            })
+            .expect("no LIE received from node 1");
+        assert_eq!(lie.link_bandwidth, Some(400));
+
+        // Reflect node 1 in the LIEs of node 2, so that the adjacency comes up.
+        let reply = ProtocolPacket {
+            header: PacketHeader {
+                major_version: PROTOCOL_MAJOR_VERSION,
+                minor_version: PROTOCOL_MINOR_VERSION,
+                sender: 2,
+                level: Some(0),
+            },
+            content: PacketContent::Lie(encoding::LIEPacket {
+                name: None,
+                local_id: 1,
+                flood_port: 20184,
+                neighbor: Some(encoding::Neighbor {
+                    originator: 1,
+                    remote_id: lie.local_id,
+                }),
+                ..lie
+            }),
+        };
+        let outer_header = OuterSecurityEnvelopeHeader::new(1.into(), 1.into(), 1.into());
+        let reply = packet::serialize(outer_header, &reply);
+        let three_way = (0..500).any(|_| {
+            node2.send_to(&reply, "127.0.0.1:20181").unwrap();
+            thread::sleep(Duration::from_millis(10));
+            network.step().unwrap();
+            network.nodes[0].links[0].lie_fsm.lie_state == LieState::ThreeWay
+        });
+        assert!(three_way);
+
+        // The Node TIEs advertise the configured metric as the cost of the adjacency, along with
+        // the configured bandwidth.
+        let neighbor = network.nodes[0]
+            .ls_db
+            .iter()
+            .find_map(|tie| match tie {
+                DBTie::Content(tie) if tie.header.tie_id.tie_type == TIESubtype::Node => {
+                    match &tie.element {
+                        TIEElement::Node(node) => node.neighbors.get(&2).cloned(),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .expect("no Node TIE originated by node 1");
+        assert_eq!(neighbor.cost, Some(3));
+        assert_eq!(neighbor.bandwidth, Some(400));
+    }
+
+    /// A network with a single node without interfaces, so that no sockets are bound.
+    fn single_node_network(level: &str) -> Network {
+        let topology = format!(
+            "
+shards:
+  - id: 0
+    nodes:
+      - name: node1
+        level: {level}
+        systemid: 1
+        interfaces: []
+"
+        );
+        let topology: TopologyDescription = serde_yaml::from_str(&topology).unwrap();
+        Network::from_desc(&topology, Passivity::Both, &FibConfig::InMemory).unwrap()
+    }
+
+    #[test]
+    fn test_set_configured_level() {
+        let mut network = single_node_network("1");
+        network.set_configured_level("node1", Some(2)).unwrap();
+        network.step().unwrap();
+        assert_eq!(network.nodes[0].ztp_fsm.level(), Some(2));
+        assert!(network.set_configured_level("node2", Some(2)).is_err());
+
+        // Configuring a level other than the leaf level on a leaf-only node clears its leaf flags,
+        // since a leaf-only node is always at the leaf level.
+        let mut network = single_node_network("leaf-to-leaf");
+        network.step().unwrap();
+        assert_eq!(network.nodes[0].ztp_fsm.level(), Some(LEAF_LEVEL));
+        network.set_configured_level("node1", Some(2)).unwrap();
+        network.step().unwrap();
+        assert_eq!(network.nodes[0].ztp_fsm.level(), Some(2));
+        assert_eq!(network.nodes[0].node_info.leaf_flags, LeafFlags::default());
+
+        // Setting the leaf flags again configures the leaf level.
+        let leaf_flags = LeafFlags {
+            leaf_only: true,
+            leaf_2_leaf: false,
+        };
+        network.set_leaf_flags("node1", leaf_flags).unwrap();
+        network.step().unwrap();
+        assert_eq!(network.nodes[0].ztp_fsm.level(), Some(LEAF_LEVEL));
+        assert_eq!(
+            network.nodes[0].node_info.configured_level,
+            Some(LEAF_LEVEL)
+        );
+    }
+
+    /// A Prefix TIE containing the given number of prefixes.
+    fn prefix_tie(num_prefixes: u32) -> ProtocolPacket {
+        let tie_id = TIEID {
+            direction: TieDirection::North,
+            originator: SystemID::try_from(1).unwrap(),
+            tie_type: TIESubtype::Prefix,
+            tie_nr: TieNumber::FIRST,
+        };
+        let prefixes = (0..num_prefixes)
+            .map(|i| {
+                let prefix = wrapper::ip_prefix(Ipv4Addr::from(i << 8).into(), 24);
+                let attributes = PrefixAttributes::new(
+                    common::DEFAULT_DISTANCE,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                (prefix, attributes)
+            })
+            .collect::<BTreeMap<_, _>>();
+        ProtocolPacket {
+            header: PacketHeader {
+                major_version: PROTOCOL_MAJOR_VERSION,
+                minor_version: PROTOCOL_MINOR_VERSION,
+                sender: 1,
+                level: Some(0),
+            },
+            content: PacketContent::Tie(TIEPacket {
+                header: TIEHeader {
+                    tieid: tie_id.into(),
+                    seq_nr: 1,
+                    origination_time: None,
+                    origination_lifetime: None,
+                },
+                element: TIEElement::Prefixes(PrefixTIEElement::new(prefixes)),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_send_tie_exceeding_mtu() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver_addr = receiver.local_addr().unwrap();
+        let mut link_socket = LinkSocket::new(
+            "if1".to_string(),
+            1,
+            "127.0.0.1:0".parse().unwrap(),
+            receiver_addr,
+            "127.0.0.1:0".parse().unwrap(),
+            400,
+        )
+        .unwrap();
+
+        let tie = prefix_tie(1);
+        assert!(link_socket
+            .send_tie(&tie, receiver_addr, common::DEFAULT_LIFETIME as u32)
+            .is_ok());
+
+        // A TIE which doesn't fit in the MTU is not sent, since the neighbor would drop it.
+        let tie = prefix_tie(100);
+        let err = link_socket
+            .send_tie(&tie, receiver_addr, common::DEFAULT_LIFETIME as u32)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
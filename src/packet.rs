@@ -14,10 +14,11 @@ use thrift::{
 
 use crate::{
     models::{
-        common::{INVALID_KEY_VALUE_KEY, UNDEFINED_NONCE, UNDEFINED_PACKET_NUMBER},
-        encoding::{ProtocolPacket, PROTOCOL_MAJOR_VERSION},
+        common::{self, INVALID_KEY_VALUE_KEY, UNDEFINED_NONCE, UNDEFINED_PACKET_NUMBER},
+        encoding::{self, ProtocolPacket, PROTOCOL_MAJOR_VERSION, PROTOCOL_MINOR_VERSION},
     },
     topology::Key,
+    wrapper::MAX_TIE_ID,
 };
 
 pub fn serialize(
@@ -37,6 +38,104 @@ pub fn serialize(
     outer_header_payload
 }
 
+/// Serialize a ProtocolPacket containing a TIE. This is the same as [serialize], except that a TIE
+/// Origin security envelope is included and the outer envelope carries the remaining lifetime of
+/// the TIE.
+pub fn serialize_tie(
+    mut outer_header: OuterSecurityEnvelopeHeader,
+    packet: &ProtocolPacket,
+    remaining_lifetime: u32,
+) -> Vec<u8> {
+    let mut packet_payload = vec![];
+    let mut binary_protocol = TBinaryOutputProtocol::new(WriteHalf::new(&mut packet_payload), true);
+    packet.write_to_out_protocol(&mut binary_protocol).unwrap();
+
+    // TODO: provide actual values for the keys
+    let tie_header = TIEOriginSecurityEnvelopeHeader::seal(None, &packet_payload);
+    outer_header.seal(
+        None,
+        &packet_payload,
+        Some((tie_header.clone(), remaining_lifetime)),
+    );
+
+    let mut payload = vec![];
+    outer_header.write(&mut payload).unwrap();
+    tie_header.write(&mut payload).unwrap();
+    payload.extend(packet_payload);
+    payload
+}
+
+/// The size of an IPv6 header plus a UDP header. IPv4 headers are smaller, so this is an upper
+/// bound on the per-datagram overhead.
+const IP_UDP_HEADER_LEN: usize = 40 + 8;
+/// The size of the Outer Security Envelope, not including the security fingerprint.
+const OUTER_ENVELOPE_LEN: usize = 16;
+/// The largest security fingerprint produced by any supported key algorithm (SHA-512).
+const MAX_FINGERPRINT_LEN: usize = 64;
+/// The size of the TIE Origin Security Envelope, not including the security fingerprint.
+const TIE_ORIGIN_ENVELOPE_LEN: usize = 4;
+
+/// Compute `TIRDEs_PER_PKT` for an interface with the given MTU. This is the number of TIE headers
+/// which can be put into a single TIDE or TIRE without the resulting datagram exceeding the MTU,
+/// assuming both the outer and the TIE Origin security envelope are present and carry the largest
+/// possible fingerprints.
+/// At least one header is always allowed, so that flooding can still make progress on links with
+/// tiny MTUs.
+pub fn tirdes_per_pkt(mtu: usize) -> usize {
+    let envelope_len = IP_UDP_HEADER_LEN
+        + OUTER_ENVELOPE_LEN
+        + MAX_FINGERPRINT_LEN
+        + TIE_ORIGIN_ENVELOPE_LEN
+        + MAX_FINGERPRINT_LEN;
+
+    // TIDEs carry a start and end range in addition to the headers, so an empty TIDE is the
+    // largest possible empty TIDE/TIRE.
+    let empty_tide = ProtocolPacket {
+        header: encoding::PacketHeader {
+            major_version: PROTOCOL_MAJOR_VERSION,
+            minor_version: PROTOCOL_MINOR_VERSION,
+            sender: 0,
+            level: Some(0),
+        },
+        content: encoding::PacketContent::Tide(encoding::TIDEPacket {
+            start_range: MAX_TIE_ID,
+            end_range: MAX_TIE_ID,
+            headers: vec![],
+        }),
+    };
+
+    // Optional fields are all present, so that this is the largest possible header.
+    let header = encoding::TIEHeaderWithLifeTime {
+        header: encoding::TIEHeader {
+            tieid: MAX_TIE_ID,
+            seq_nr: 0,
+            origination_time: Some(common::IEEE8021ASTimeStampType {
+                a_s_sec: 0,
+                a_s_nsec: Some(0),
+            }),
+            origination_lifetime: Some(0),
+        },
+        remaining_lifetime: 0,
+    };
+
+    let available = mtu.saturating_sub(envelope_len + thrift_len(&empty_tide));
+    (available / thrift_len(&header)).max(1)
+}
+
+/// The largest RIFT packet (including its security envelopes) which can be sent over an interface
+/// with the given MTU.
+pub fn max_packet_len(mtu: usize) -> usize {
+    mtu.saturating_sub(IP_UDP_HEADER_LEN)
+}
+
+/// The length of the given value when encoded with the thrift binary protocol.
+fn thrift_len(value: &impl TSerializable) -> usize {
+    let mut buf = vec![];
+    let mut binary_protocol = TBinaryOutputProtocol::new(WriteHalf::new(&mut buf), true);
+    value.write_to_out_protocol(&mut binary_protocol).unwrap();
+    buf.len()
+}
+
 // Parse a ProtocolPacket contained in a security envelope.
 // The returned tuple consists of three things. First is the header of the outer security envelope.
 // If a TIE Origin security envelope present, that is also returned. Finally, the unconsumed
@@ -563,9 +662,19 @@ impl std::error::Error for ParsingError {
 mod test {
     use std::borrow::Cow;
 
-    use crate::packet::TIEOriginSecurityEnvelopeHeader;
+    use crate::{
+        models::{
+            common,
+            encoding::{self, ProtocolPacket, PROTOCOL_MAJOR_VERSION, PROTOCOL_MINOR_VERSION},
+        },
+        packet::TIEOriginSecurityEnvelopeHeader,
+        wrapper::MAX_TIE_ID,
+    };
 
-    use super::{KeyID, OuterSecurityEnvelopeHeader, PacketNumber};
+    use super::{
+        serialize, tirdes_per_pkt, KeyID, OuterSecurityEnvelopeHeader, PacketNumber,
+        IP_UDP_HEADER_LEN, MAX_FINGERPRINT_LEN, TIE_ORIGIN_ENVELOPE_LEN,
+    };
 
     #[test]
     fn test_deserialize_outer_and_tie_envelopes() {
@@ -761,4 +870,50 @@ mod test {
         actual_packet.extend(actual_protocol_data);
         assert_eq!(&packet, &actual_packet[..]);
     }
+
+    #[test]
+    fn test_tirdes_per_pkt_fits_mtu() {
+        let header = encoding::TIEHeaderWithLifeTime {
+            header: encoding::TIEHeader {
+                tieid: MAX_TIE_ID,
+                seq_nr: 0,
+                origination_time: Some(common::IEEE8021ASTimeStampType {
+                    a_s_sec: 0,
+                    a_s_nsec: Some(0),
+                }),
+                origination_lifetime: Some(0),
+            },
+            remaining_lifetime: 0,
+        };
+        let datagram_len = |num_headers: usize| {
+            let tide = ProtocolPacket {
+                header: encoding::PacketHeader {
+                    major_version: PROTOCOL_MAJOR_VERSION,
+                    minor_version: PROTOCOL_MINOR_VERSION,
+                    sender: 0,
+                    level: Some(0),
+                },
+                content: encoding::PacketContent::Tide(encoding::TIDEPacket {
+                    start_range: MAX_TIE_ID,
+                    end_range: MAX_TIE_ID,
+                    headers: vec![header.clone(); num_headers],
+                }),
+            };
+            // The serialized packet includes the outer envelope, but without a fingerprint or a TIE
+            // Origin envelope.
+            let outer_header = OuterSecurityEnvelopeHeader::new(1.into(), 1.into(), 1.into());
+            IP_UDP_HEADER_LEN
+                + MAX_FINGERPRINT_LEN
+                + TIE_ORIGIN_ENVELOPE_LEN
+                + MAX_FINGERPRINT_LEN
+                + serialize(outer_header, &tide).len()
+        };
+
+        for mtu in [576, 1400, 1500, 9000] {
+            let num_headers = tirdes_per_pkt(mtu);
+            assert!(datagram_len(num_headers) <= mtu);
+            assert!(datagram_len(num_headers + 1) > mtu);
+        }
+        assert_eq!(tirdes_per_pkt(0), 1);
+    }
 }
@@ -0,0 +1,400 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
+
+use crate::{
+    models::common::IPPrefixType,
+    spf::{NextHop, Route},
+};
+
+/// A next hop as installed in the FIB. Unlike a [NextHop], which identifies the neighbor and link
+/// by RIFT identifiers, this identifies the neighbor by its address and the link by the name of the
+/// interface.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FibNextHop {
+    pub address: IpAddr,
+    pub interface: String,
+    /// The relative share of traffic sent to this next hop, such as the bandwidth of the link. If
+    /// None, traffic is split equally.
+    pub weight: Option<u32>,
+}
+
+/// A forwarding table which routes can be installed into and withdrawn from. Installing a route for
+/// a prefix replaces any route previously installed for that prefix. Installing a route without
+/// any next hops makes the prefix unreachable.
+pub trait FibBackend {
+    fn install(
+        &mut self,
+        prefix: &IPPrefixType,
+        next_hops: &BTreeSet<FibNextHop>,
+    ) -> io::Result<()>;
+    fn withdraw(&mut self, prefix: &IPPrefixType) -> io::Result<()>;
+}
+
+/// A FIB which just keeps the installed routes in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryFib {
+    pub routes: BTreeMap<IPPrefixType, BTreeSet<FibNextHop>>,
+}
+
+impl FibBackend for InMemoryFib {
+    fn install(
+        &mut self,
+        prefix: &IPPrefixType,
+        next_hops: &BTreeSet<FibNextHop>,
+    ) -> io::Result<()> {
+        self.routes.insert(prefix.clone(), next_hops.clone());
+        Ok(())
+    }
+
+    fn withdraw(&mut self, prefix: &IPPrefixType) -> io::Result<()> {
+        self.routes.remove(prefix);
+        Ok(())
+    }
+}
+
+/// A FIB which writes every change as an `ip route` command to a file. The file is suitable for
+/// passing to `ip -batch`, which makes it possible to replay the routes into the kernel of a plain
+/// Linux machine without the daemon itself needing netlink privileges.
+pub struct IpBatchFib {
+    file: File,
+}
+
+impl IpBatchFib {
+    /// Create a new batch file at the given path, truncating it if it already exists.
+    pub fn new(path: PathBuf) -> io::Result<IpBatchFib> {
+        Ok(IpBatchFib {
+            file: File::create(path)?,
+        })
+    }
+}
+
+impl FibBackend for IpBatchFib {
+    fn install(
+        &mut self,
+        prefix: &IPPrefixType,
+        next_hops: &BTreeSet<FibNextHop>,
+    ) -> io::Result<()> {
+        // The route type goes before the prefix, as in `route replace unreachable 10.0.0.0/8`.
+        let mut command = if next_hops.is_empty() {
+            format!("route replace unreachable {}", format_prefix(prefix))
+        } else {
+            format!("route replace {}", format_prefix(prefix))
+        };
+        let weights = kernel_weights(next_hops);
+        for (next_hop, weight) in next_hops.iter().zip(weights) {
+            command += &format!(
+                " nexthop via {} dev {}",
+                next_hop.address, next_hop.interface
+            );
+            if let Some(weight) = weight {
+                command += &format!(" weight {}", weight);
+            }
+        }
+        writeln!(self.file, "{}", command)?;
+        self.file.flush()
+    }
+
+    fn withdraw(&mut self, prefix: &IPPrefixType) -> io::Result<()> {
+        writeln!(self.file, "route del {}", format_prefix(prefix))?;
+        self.file.flush()
+    }
+}
+
+/// The kernel only accepts next hop weights from 1 to 256, so the weights of the next hops are
+/// scaled such that the largest weight becomes 256. This keeps the ratios between the weights,
+/// except that weights which would round down to 0 become 1.
+fn kernel_weights(next_hops: &BTreeSet<FibNextHop>) -> Vec<Option<u32>> {
+    let max = next_hops
+        .iter()
+        .filter_map(|next_hop| next_hop.weight)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    next_hops
+        .iter()
+        .map(|next_hop| {
+            next_hop.weight.map(|weight| {
+                let scaled = (u64::from(weight) * 256 + u64::from(max) / 2) / u64::from(max);
+                scaled.clamp(1, 256) as u32
+            })
+        })
+        .collect()
+}
+
+/// Determines which [FibBackend] each node installs its routes into.
+#[derive(Debug, Clone, Default)]
+pub enum FibConfig {
+    /// Keep the routes in memory only.
+    #[default]
+    InMemory,
+    /// Write the routes of each node to `<directory>/<node name>.batch` (see [IpBatchFib]).
+    IpBatch(PathBuf),
+}
+
+impl FibConfig {
+    /// Create the backend for the node with the given name.
+    pub fn backend(&self, node_name: &str) -> io::Result<Box<dyn FibBackend>> {
+        match self {
+            FibConfig::InMemory => Ok(Box::new(InMemoryFib::default())),
+            FibConfig::IpBatch(directory) => {
+                let path = directory.join(format!("{}.batch", node_name));
+                Ok(Box::new(IpBatchFib::new(path)?))
+            }
+        }
+    }
+}
+
+/// The routing information base of a node. This stores the routes computed by the SPF and keeps
+/// the FIB in sync with them, installing and withdrawing only the routes which actually changed.
+pub struct Rib {
+    /// The routes as computed by the SPF.
+    routes: BTreeMap<IPPrefixType, Route>,
+    /// The routes currently installed in the FIB.
+    installed: BTreeMap<IPPrefixType, BTreeSet<FibNextHop>>,
+    backend: Box<dyn FibBackend>,
+}
+
+impl Rib {
+    pub fn new(backend: Box<dyn FibBackend>) -> Rib {
+        Rib {
+            routes: BTreeMap::new(),
+            installed: BTreeMap::new(),
+            backend,
+        }
+    }
+
+    /// The routes as computed by the most recent SPF.
+    pub fn routes(&self) -> &BTreeMap<IPPrefixType, Route> {
+        &self.routes
+    }
+
+    /// Replace the routes in the RIB and update the FIB to match. `resolve` converts the next hops
+    /// of the routes into FIB next hops, returning None if the next hop can't be used (for
+    /// instance, because the link went down). Next hops whose address family doesn't match the
+    /// prefix are not installed, and routes without any usable next hops are withdrawn. Routes
+    /// which have no next hops to begin with (such as negatively disaggregated prefixes) are
+    /// installed as unreachable.
+    pub fn update(
+        &mut self,
+        routes: BTreeMap<IPPrefixType, Route>,
+        resolve: impl Fn(&NextHop) -> Option<FibNextHop>,
+    ) -> io::Result<()> {
+        let mut fib_routes = BTreeMap::new();
+        for (prefix, route) in &routes {
+            let next_hops = route
+                .next_hops
+                .iter()
+                .filter_map(&resolve)
+                .filter(|next_hop| is_same_family(prefix, &next_hop.address))
+                .collect::<BTreeSet<_>>();
+            if route.next_hops.is_empty() || !next_hops.is_empty() {
+                fib_routes.insert(prefix.clone(), next_hops);
+            }
+        }
+        self.routes = routes;
+
+        // Withdraw the routes which are gone, then install the routes which are new or changed.
+        let withdrawn = self
+            .installed
+            .keys()
+            .filter(|prefix| !fib_routes.contains_key(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        for prefix in withdrawn {
+            self.backend.withdraw(&prefix)?;
+            self.installed.remove(&prefix);
+        }
+        for (prefix, next_hops) in fib_routes {
+            if self.installed.get(&prefix) != Some(&next_hops) {
+                self.backend.install(&prefix, &next_hops)?;
+                self.installed.insert(prefix, next_hops);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_same_family(prefix: &IPPrefixType, address: &IpAddr) -> bool {
+    matches!(
+        (prefix, address),
+        (IPPrefixType::Ipv4prefix(_), IpAddr::V4(_)) | (IPPrefixType::Ipv6prefix(_), IpAddr::V6(_))
+    )
+}
+
+/// Format the prefix in the usual `address/length` notation.
+fn format_prefix(prefix: &IPPrefixType) -> String {
+    match prefix {
+        IPPrefixType::Ipv4prefix(prefix) => {
+            let address = Ipv4Addr::from(prefix.address as u32);
+            format!("{}/{}", address, prefix.prefixlen)
+        }
+        IPPrefixType::Ipv6prefix(prefix) => {
+            let mut octets = [0; 16];
+            let len = prefix.address.len().min(16);
+            octets[..len].copy_from_slice(&prefix.address[..len]);
+            format!("{}/{}", Ipv6Addr::from(octets), prefix.prefixlen)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::RefCell,
+        collections::{BTreeMap, BTreeSet},
+        fs, io,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        rc::Rc,
+    };
+
+    use crate::{
+        models::common::IPPrefixType,
+        spf::{NextHop, Route},
+        wrapper::{self, SystemID},
+    };
+
+    use super::{
+        format_prefix, kernel_weights, FibBackend, FibNextHop, InMemoryFib, IpBatchFib, Rib,
+    };
+
+    /// An in-memory FIB which can still be inspected after being handed to the RIB.
+    #[derive(Clone, Default)]
+    struct SharedFib(Rc<RefCell<InMemoryFib>>);
+
+    impl FibBackend for SharedFib {
+        fn install(
+            &mut self,
+            prefix: &IPPrefixType,
+            next_hops: &BTreeSet<FibNextHop>,
+        ) -> io::Result<()> {
+            self.0.borrow_mut().install(prefix, next_hops)
+        }
+
+        fn withdraw(&mut self, prefix: &IPPrefixType) -> io::Result<()> {
+            self.0.borrow_mut().withdraw(prefix)
+        }
+    }
+
+    fn next_hop(system_id: i64) -> NextHop {
+        NextHop {
+            system_id: SystemID::try_from(system_id).unwrap(),
+            link_id: system_id as i32,
+            weight: None,
+        }
+    }
+
+    fn route(next_hops: &[i64]) -> Route {
+        Route {
+            metric: 1,
+            next_hops: next_hops.iter().map(|&id| next_hop(id)).collect(),
+        }
+    }
+
+    /// Next hop N resolves to 10.0.0.N on interface ifN. Next hop 9 doesn't resolve.
+    fn resolve(next_hop: &NextHop) -> Option<FibNextHop> {
+        let id = next_hop.system_id.get() as u8;
+        (id != 9).then(|| FibNextHop {
+            address: IpAddr::V4(Ipv4Addr::new(10, 0, 0, id)),
+            interface: format!("if{}", id),
+            weight: None,
+        })
+    }
+
+    fn v4_prefix(a: u8) -> IPPrefixType {
+        wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::new(a, 0, 0, 0)), 8)
+    }
+
+    #[test]
+    fn test_rib_update() {
+        let fib = SharedFib::default();
+        let mut rib = Rib::new(Box::new(fib.clone()));
+        let v6_prefix = wrapper::ip_prefix(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+
+        let routes = BTreeMap::from([
+            (v4_prefix(1), route(&[1, 2])),
+            (v4_prefix(2), route(&[9])),
+            (v6_prefix.clone(), route(&[1])),
+        ]);
+        rib.update(routes.clone(), resolve).unwrap();
+        assert_eq!(rib.routes(), &routes);
+        // Unresolvable next hops and next hops of the wrong address family are not installed.
+        let installed = fib.0.borrow().routes.clone();
+        assert_eq!(installed.keys().collect::<Vec<_>>(), vec![&v4_prefix(1)]);
+        assert_eq!(installed[&v4_prefix(1)].len(), 2);
+
+        let routes = BTreeMap::from([(v4_prefix(3), route(&[2]))]);
+        rib.update(routes, resolve).unwrap();
+        let installed = fib.0.borrow().routes.clone();
+        assert_eq!(installed.keys().collect::<Vec<_>>(), vec![&v4_prefix(3)]);
+        assert_eq!(
+            installed[&v4_prefix(3)],
+            BTreeSet::from([FibNextHop {
+                address: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+                interface: "if2".to_string(),
+                weight: None,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_ip_batch_fib() {
+        let path = std::env::temp_dir().join(format!("rift-rust-{}.batch", std::process::id()));
+        let mut fib = IpBatchFib::new(path.clone()).unwrap();
+        let next_hops = [1, 2]
+            .into_iter()
+            .filter_map(|id| resolve(&next_hop(id)))
+            .collect();
+        fib.install(&v4_prefix(1), &next_hops).unwrap();
+        fib.install(&v4_prefix(2), &BTreeSet::new()).unwrap();
+        fib.withdraw(&v4_prefix(1)).unwrap();
+
+        let batch = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            batch,
+            "route replace 1.0.0.0/8 nexthop via 10.0.0.1 dev if1 nexthop via 10.0.0.2 dev if2\n\
+             route replace unreachable 2.0.0.0/8\n\
+             route del 1.0.0.0/8\n"
+        );
+    }
+
+    #[test]
+    fn test_format_prefix() {
+        assert_eq!(format_prefix(&v4_prefix(10)), "10.0.0.0/8");
+        let prefix = wrapper::ip_prefix(IpAddr::V6("2001:db8::".parse().unwrap()), 32);
+        assert_eq!(format_prefix(&prefix), "2001:db8::/32");
+    }
+
+    #[test]
+    fn test_kernel_weights() {
+        let next_hops = |weights: &[u32]| {
+            weights
+                .iter()
+                .zip(1..)
+                .map(|(&weight, id)| FibNextHop {
+                    address: IpAddr::V4(Ipv4Addr::new(10, 0, 0, id)),
+                    interface: format!("if{}", id),
+                    weight: Some(weight),
+                })
+                .collect()
+        };
+        assert_eq!(
+            kernel_weights(&next_hops(&[100, 150, 1000])),
+            vec![Some(26), Some(38), Some(256)]
+        );
+        assert_eq!(
+            kernel_weights(&next_hops(&[100, 150, 100_000])),
+            vec![Some(1), Some(1), Some(256)]
+        );
+        assert_eq!(
+            kernel_weights(&next_hops(&[1, 2])),
+            vec![Some(128), Some(256)]
+        );
+        assert_eq!(kernel_weights(&next_hops(&[u32::MAX])), vec![Some(256)]);
+    }
+}
@@ -11,11 +11,16 @@ use crate::{
 pub trait RiftSocket {
     fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
     fn send(&self, buf: &[u8]) -> io::Result<usize>;
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize>;
     fn get(&self) -> &UdpSocket;
 
     /// Receive one packet from the given socket.
     fn recv_packet<'a>(&self, buf: &'a mut [u8], keys: &SecretKeyStore) -> RecvPacketResult<'a> {
         match self.recv_from(buf) {
+            Ok((length, _)) if length == buf.len() => {
+                tracing::warn!(length, "dropping packet which does not fit in the MTU");
+                RecvPacketResult::NoPacket
+            }
             Ok((length, address)) => {
                 // Remove excess zeros from bytes vector.
                 let buf = &buf[..length];
@@ -49,6 +54,10 @@ impl RiftSocket for UdpSocket {
         self.send(buf)
     }
 
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.send_to(buf, addr)
+    }
+
     fn get(&self) -> &UdpSocket {
         self
     }
@@ -88,6 +97,15 @@ impl RiftSocket for ChaosSocket {
         }
     }
 
+    fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        if rand::random::<f32>() < self.send_fail_chance {
+            tracing::debug!("send fail!");
+            Ok(buf.len())
+        } else {
+            self.socket.send_to(buf, addr)
+        }
+    }
+
     fn get(&self) -> &UdpSocket {
         &self.socket
     }
@@ -122,3 +140,40 @@ impl From<ParsingError> for RecvPacketError {
         RecvPacketError::ParsingError(err)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, net::UdpSocket};
+
+    use crate::packet::SecretKeyStore;
+
+    use super::{RecvPacketResult, RiftSocket};
+
+    #[test]
+    fn test_recv_truncated_packet() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let keys = SecretKeyStore::new(HashMap::new());
+
+        // A packet which fills the entire buffer may have been truncated, so it is dropped without
+        // being parsed.
+        let mut buf = vec![0; 64];
+        sender
+            .send_to(&[0xff; 64], receiver.local_addr().unwrap())
+            .unwrap();
+        assert!(matches!(
+            receiver.recv_packet(&mut buf, &keys),
+            RecvPacketResult::NoPacket
+        ));
+
+        // A packet which is shorter than the buffer is parsed (and rejected, since it is garbage).
+        let mut buf = vec![0; 65];
+        sender
+            .send_to(&[0xff; 64], receiver.local_addr().unwrap())
+            .unwrap();
+        assert!(matches!(
+            receiver.recv_packet(&mut buf, &keys),
+            RecvPacketResult::Err(_)
+        ));
+    }
+}
@@ -0,0 +1,966 @@
+use std::{
+    cmp::Reverse,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, BinaryHeap},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{
+    models::{
+        common::{self, IPPrefixType, LinkIDType, MetricType},
+        encoding::{self, NodeNeighborsTIEElement, PrefixAttributes},
+    },
+    tie_exchange::{DBTie, LinkStateDatabase},
+    wrapper::{self, SystemID, TIESubtype, TieDirection},
+};
+
+/// A next hop of a route. This is the neighbor the traffic is sent to, along with the local link
+/// the traffic is sent over. Parallel links to the same neighbor result in multiple next hops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NextHop {
+    pub system_id: SystemID,
+    pub link_id: LinkIDType,
+    /// The bandwidth available over this link, which is used to weight the next hops of a route
+    /// with bandwidth-weighted ECMP. None if the root advertises no bandwidth for the neighbor.
+    pub weight: Option<u32>,
+}
+
+/// A route to a prefix, as computed by the SPF. All of the next hops have the same (lowest) cost,
+/// so traffic may be spread across all of them (ECMP).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub metric: MetricType,
+    pub next_hops: BTreeSet<NextHop>,
+}
+
+impl Route {
+    /// Add a path with the given metric and next hops to this route. If the path is cheaper than
+    /// the route, it replaces the route entirely. If the path costs the same, its next hops are
+    /// added to the route. More expensive paths are ignored.
+    fn add_path(&mut self, metric: MetricType, next_hops: &BTreeSet<NextHop>) {
+        if metric < self.metric {
+            self.metric = metric;
+            self.next_hops = next_hops.clone();
+        } else if metric == self.metric {
+            self.next_hops.extend(next_hops);
+        }
+    }
+}
+
+/// The direction an SPF is run in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpfDirection {
+    /// N-SPF, which computes routes towards the north (such as default routes).
+    North,
+    /// S-SPF, which computes routes towards prefixes south of this node.
+    South,
+}
+
+/// The union of all Node TIEs with a given originator and direction.
+struct NodeView {
+    level: common::LevelType,
+    neighbors: BTreeMap<common::SystemIDType, NodeNeighborsTIEElement>,
+    same_plane_tofs: BTreeSet<common::SystemIDType>,
+}
+
+/// The Node and Prefix TIEs in the LSDB, indexed by originator and direction.
+struct Topology<'a> {
+    nodes: BTreeMap<(SystemID, TieDirection), NodeView>,
+    prefixes: Vec<(SystemID, TieDirection, &'a encoding::PrefixTIEElement)>,
+    /// The South Negative Disaggregation Prefix TIEs, indexed by originator.
+    negative_prefixes: Vec<(SystemID, &'a encoding::PrefixTIEElement)>,
+}
+
+impl<'a> Topology<'a> {
+    fn new(ls_db: &'a LinkStateDatabase) -> Topology<'a> {
+        let mut nodes: BTreeMap<_, NodeView> = BTreeMap::new();
+        let mut prefixes = vec![];
+        let mut negative_prefixes = vec![];
+        for tie in ls_db.iter() {
+            let DBTie::Content(tie) = tie else {
+                continue;
+            };
+            let tie_id = tie.header.tie_id;
+            match (&tie.element, tie_id.tie_type) {
+                (encoding::TIEElement::Node(node), _) => {
+                    let view = nodes
+                        .entry((tie_id.originator, tie_id.direction))
+                        .or_insert_with(|| NodeView {
+                            level: node.level,
+                            neighbors: BTreeMap::new(),
+                            same_plane_tofs: BTreeSet::new(),
+                        });
+                    view.neighbors.extend(node.neighbors.clone());
+                    view.same_plane_tofs
+                        .extend(node.same_plane_tofs.iter().flatten());
+                }
+                (encoding::TIEElement::Prefixes(element), TIESubtype::Prefix)
+                | (
+                    encoding::TIEElement::PositiveDisaggregationPrefixes(element),
+                    TIESubtype::PositiveDisaggregationPrefix,
+                ) => {
+                    prefixes.push((tie_id.originator, tie_id.direction, element));
+                }
+                (
+                    encoding::TIEElement::NegativeDisaggregationPrefixes(element),
+                    TIESubtype::NegativeDisaggregationPrefix,
+                ) if tie_id.direction == TieDirection::South => {
+                    negative_prefixes.push((tie_id.originator, element));
+                }
+                _ => (),
+            }
+        }
+        Topology {
+            nodes,
+            prefixes,
+            negative_prefixes,
+        }
+    }
+
+    /// Returns the Node TIE of the given node, preferring the one in the given direction.
+    fn node(&self, system_id: SystemID, direction: TieDirection) -> Option<&NodeView> {
+        let other = match direction {
+            TieDirection::North => TieDirection::South,
+            TieDirection::South => TieDirection::North,
+        };
+        self.nodes
+            .get(&(system_id, direction))
+            .or_else(|| self.nodes.get(&(system_id, other)))
+    }
+
+    /// Returns true if the adjacency from `from` to `to` (which `from` claims is at `to_level`)
+    /// exists in both directions. That is, `to` must be at `to_level` and must list `from` as a
+    /// neighbor at `from_level`. An adjacency is only used by the SPF if both sides agree on it.
+    fn is_bidirectional(
+        &self,
+        (from, from_level): (SystemID, common::LevelType),
+        (to, to_level): (SystemID, common::LevelType),
+        backlink_direction: TieDirection,
+    ) -> bool {
+        let Some(to_node) = self.node(to, backlink_direction) else {
+            return false;
+        };
+        to_node.level == to_level
+            && to_node
+                .neighbors
+                .get(&from.get())
+                .is_some_and(|neighbor| neighbor.level == from_level)
+    }
+
+    /// Returns the other nodes on the same level as `root` which share a southbound neighbor with
+    /// it. These are found by looking at the northbound neighbors of every southbound neighbor of
+    /// `root`, as advertised in the North Node TIEs of the southbound neighbors.
+    fn same_level_peers(&self, root: SystemID) -> BTreeSet<SystemID> {
+        let Some(root_node) = self.node(root, TieDirection::South) else {
+            return BTreeSet::new();
+        };
+
+        let mut peers = BTreeSet::new();
+        for (&neighbor_id, neighbor) in &root_node.neighbors {
+            let Ok(neighbor_id) = SystemID::try_from(neighbor_id) else {
+                continue;
+            };
+            if neighbor.level >= root_node.level {
+                continue;
+            }
+            let Some(neighbor_node) = self.node(neighbor_id, TieDirection::North) else {
+                continue;
+            };
+            let neighbor_peers = neighbor_node
+                .neighbors
+                .iter()
+                .filter(|(_, peer)| peer.level == root_node.level)
+                .filter_map(|(&peer_id, _)| SystemID::try_from(peer_id).ok());
+            peers.extend(neighbor_peers);
+        }
+        peers.remove(&root);
+        peers
+    }
+}
+
+/// Run an SPF rooted at `root` over the Node TIEs in the LSDB and return the routes to every
+/// prefix found in the Prefix TIEs of the reachable nodes.
+/// S-SPF only follows southbound adjacencies in South Node TIEs, and uses the North Node TIEs of
+/// the lower node to check for backlink connectivity. The routes are computed from the North Prefix
+/// TIEs of the nodes reached.
+/// N-SPF only follows northbound adjacencies in North Node TIEs. East-West adjacencies of the root
+/// are also used, but only if the root has no northbound adjacencies at all (in which case any
+/// default route found by crossing the East-West link is the only way north). The routes are
+/// computed from the South Prefix TIEs and Positive Disaggregation Prefix TIEs of the nodes
+/// reached.
+pub fn compute_routes(
+    ls_db: &LinkStateDatabase,
+    root: SystemID,
+    direction: SpfDirection,
+) -> BTreeMap<IPPrefixType, Route> {
+    spf(&Topology::new(ls_db), root, direction)
+}
+
+/// 6.5.1. Positive, Non-transitive Disaggregation
+/// Returns the prefixes which the node `root` should disaggregate southbound, given the routes
+/// computed by its S-SPF. Only the other nodes on the same level which share a southbound neighbor
+/// with `root` are considered, since only their southbound neighbors could pick them instead of
+/// `root` by way of the default route. Their South Node TIEs are reflected by the nodes below. An
+/// S-SPF is run from the point of view of each of them, and every prefix which `root` can reach but
+/// one of those nodes can't is disaggregated. This ensures that nodes below don't send traffic for
+/// the prefix to a node which can't deliver it.
+pub fn positive_disaggregation(
+    ls_db: &LinkStateDatabase,
+    root: SystemID,
+    south_routes: &BTreeMap<IPPrefixType, Route>,
+) -> BTreeMap<IPPrefixType, PrefixAttributes> {
+    let topology = Topology::new(ls_db);
+
+    let mut disaggregated = BTreeMap::new();
+    for peer in topology.same_level_peers(root) {
+        // Without the South Node TIE of the peer, its southbound reachability is unknown.
+        if !topology.nodes.contains_key(&(peer, TieDirection::South)) {
+            continue;
+        }
+        let peer_routes = spf(&topology, peer, SpfDirection::South);
+        for (prefix, route) in south_routes {
+            if !peer_routes.contains_key(prefix) {
+                let attributes =
+                    PrefixAttributes::new(route.metric, None, None, None, None, None, None);
+                disaggregated.insert(prefix.clone(), attributes);
+            }
+        }
+    }
+    disaggregated
+}
+
+/// Returns the other ToF nodes which the ToF node `root` itself sees in its plane. These are the ToF
+/// nodes which share a southbound neighbor with `root`, since nodes in the same plane share
+/// southbound neighbors (whereas nodes in different planes don't). This is the set `root`
+/// advertises in the `same_plane_tofs` of its Node TIEs.
+pub fn same_plane_tofs(ls_db: &LinkStateDatabase, root: SystemID) -> BTreeSet<SystemID> {
+    Topology::new(ls_db).same_level_peers(root)
+}
+
+/// Returns all ToF nodes in the plane of `root`, given the ToF nodes `root` sees in its plane itself.
+/// A ToF node is in the plane if a ToF node in the plane advertises it in its `same_plane_tofs`, or
+/// if it advertises a ToF node in the plane itself. Since every ToF node only advertises the ToF
+/// nodes it sees itself, this also finds ToF nodes which share no southbound neighbor with `root`.
+fn plane_tofs(
+    topology: &Topology,
+    root: SystemID,
+    level: common::LevelType,
+    same_plane_tofs: &BTreeSet<SystemID>,
+) -> BTreeSet<SystemID> {
+    let advertised = topology
+        .nodes
+        .iter()
+        .filter(|(&(system_id, _), node)| system_id != root && node.level == level)
+        .map(|(&(system_id, _), node)| {
+            let tofs = node
+                .same_plane_tofs
+                .iter()
+                .filter_map(|&tof| SystemID::try_from(tof).ok())
+                .collect::<BTreeSet<_>>();
+            (system_id, tofs)
+        })
+        .collect::<Vec<_>>();
+
+    let mut plane = same_plane_tofs.clone();
+    plane.insert(root);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (tof, tofs) in &advertised {
+            if plane.contains(tof) {
+                for &other in tofs {
+                    changed |= plane.insert(other);
+                }
+            } else if !tofs.is_disjoint(&plane) {
+                changed |= plane.insert(*tof);
+            }
+        }
+    }
+    plane.remove(&root);
+    plane
+}
+
+/// 6.5.2. Negative, Transitive Disaggregation for Fallen Leaves
+/// Returns the prefixes which the ToF node `root` should negatively disaggregate. These are the
+/// prefixes which some ToF node in another plane can reach southbound, but which no ToF node in
+/// the plane of `root` can reach. The plane is made up of the ToF nodes `root` sees itself (given by
+/// `same_plane_tofs`) and those the other ToF nodes in the plane advertise. Traffic for such prefixes
+/// must not be sent into this plane at all, so nodes below need to punch a hole in their default
+/// route.
+pub fn tof_negative_disaggregation(
+    ls_db: &LinkStateDatabase,
+    root: SystemID,
+    same_plane_tofs: &BTreeSet<SystemID>,
+) -> BTreeMap<IPPrefixType, PrefixAttributes> {
+    let topology = Topology::new(ls_db);
+    let Some(root_node) = topology.node(root, TieDirection::South) else {
+        return BTreeMap::new();
+    };
+
+    let tofs = topology
+        .nodes
+        .iter()
+        .filter(|(_, node)| node.level == root_node.level)
+        .map(|(&(system_id, _), _)| system_id)
+        .collect::<BTreeSet<_>>();
+    let same_plane_tofs = plane_tofs(&topology, root, root_node.level, same_plane_tofs);
+
+    let mut reachable_in_plane = BTreeSet::new();
+    for &tof in tofs
+        .iter()
+        .filter(|&tof| *tof == root || same_plane_tofs.contains(tof))
+    {
+        reachable_in_plane.extend(spf(&topology, tof, SpfDirection::South).into_keys());
+    }
+
+    let mut disaggregated = BTreeMap::new();
+    for &tof in tofs
+        .iter()
+        .filter(|&tof| *tof != root && !same_plane_tofs.contains(tof))
+    {
+        for prefix in spf(&topology, tof, SpfDirection::South).into_keys() {
+            if !reachable_in_plane.contains(&prefix) {
+                disaggregated.insert(prefix, negative_prefix_attributes());
+            }
+        }
+    }
+    disaggregated
+}
+
+/// Apply the Negative Disaggregation Prefix TIEs originated by the northbound neighbors of `root`
+/// to the routes computed by its N-SPF. For each negatively disaggregated prefix, a route is added
+/// which uses the next hops of the default route, except for the neighbors which negatively
+/// disaggregated the prefix. If that leaves no next hops at all, the route is unreachable (it has no
+/// next hops), and the prefix is returned so that `root` negatively disaggregates it in turn.
+pub fn apply_negative_disaggregation(
+    ls_db: &LinkStateDatabase,
+    root: SystemID,
+    north_routes: &mut BTreeMap<IPPrefixType, Route>,
+) -> BTreeMap<IPPrefixType, PrefixAttributes> {
+    let topology = Topology::new(ls_db);
+    let Some(root_node) = topology.node(root, TieDirection::North) else {
+        return BTreeMap::new();
+    };
+    let northbound_neighbors = root_node
+        .neighbors
+        .iter()
+        .filter(|(_, neighbor)| neighbor.level > root_node.level)
+        .filter_map(|(&neighbor_id, _)| SystemID::try_from(neighbor_id).ok())
+        .collect::<BTreeSet<_>>();
+
+    let mut excluded: BTreeMap<&IPPrefixType, BTreeSet<SystemID>> = BTreeMap::new();
+    for &(originator, element) in &topology.negative_prefixes {
+        if !northbound_neighbors.contains(&originator) {
+            continue;
+        }
+        for prefix in element.prefixes.keys() {
+            excluded.entry(prefix).or_default().insert(originator);
+        }
+    }
+
+    let mut disaggregated = BTreeMap::new();
+    for (prefix, originators) in excluded {
+        // Positively disaggregated routes are more specific already, so leave them alone.
+        if north_routes.contains_key(prefix) {
+            continue;
+        }
+        let default_prefix = match prefix {
+            IPPrefixType::Ipv4prefix(_) => wrapper::ip_prefix(Ipv4Addr::UNSPECIFIED.into(), 0),
+            IPPrefixType::Ipv6prefix(_) => wrapper::ip_prefix(Ipv6Addr::UNSPECIFIED.into(), 0),
+        };
+        let Some(default_route) = north_routes.get(&default_prefix) else {
+            continue;
+        };
+
+        let next_hops = default_route
+            .next_hops
+            .iter()
+            .filter(|next_hop| !originators.contains(&next_hop.system_id))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let route = if next_hops.is_empty() {
+            disaggregated.insert(prefix.clone(), negative_prefix_attributes());
+            Route {
+                metric: common::INFINITE_DISTANCE,
+                next_hops,
+            }
+        } else {
+            Route {
+                metric: default_route.metric,
+                next_hops,
+            }
+        };
+        north_routes.insert(prefix.clone(), route);
+    }
+    disaggregated
+}
+
+/// The metric of a negatively disaggregated prefix is meaningless, since the prefix is unreachable.
+fn negative_prefix_attributes() -> PrefixAttributes {
+    PrefixAttributes::new(
+        common::INFINITE_DISTANCE,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+}
+
+fn spf(
+    topology: &Topology,
+    root: SystemID,
+    direction: SpfDirection,
+) -> BTreeMap<IPPrefixType, Route> {
+    let (tie_direction, backlink_direction) = match direction {
+        SpfDirection::North => (TieDirection::North, TieDirection::South),
+        SpfDirection::South => (TieDirection::South, TieDirection::North),
+    };
+
+    let Some(root_node) = topology.node(root, tie_direction) else {
+        return BTreeMap::new();
+    };
+    let root_has_northbound = root_node
+        .neighbors
+        .values()
+        .any(|neighbor| neighbor.level > root_node.level);
+
+    // Returns true if the adjacency from a node on `from_level` to a node on `to_level` may be
+    // followed by this SPF.
+    let is_allowed = |from: SystemID, from_level, to_level| match direction {
+        SpfDirection::North => {
+            to_level > from_level
+                || (from == root && !root_has_northbound && to_level == from_level)
+        }
+        // 4.3.9. A leaf may follow its East-West adjacencies to other leaves in the S-SPF.
+        SpfDirection::South => {
+            to_level < from_level
+                || (from == root && from_level == common::LEAF_LEVEL && to_level == from_level)
+        }
+    };
+
+    // The cost and next hops of the best paths to each node found so far.
+    let mut paths: BTreeMap<SystemID, (MetricType, BTreeSet<NextHop>)> = BTreeMap::new();
+    let mut visited = BTreeSet::new();
+    let mut candidates = BinaryHeap::new();
+    paths.insert(root, (0, BTreeSet::new()));
+    candidates.push(Reverse((0 as MetricType, root)));
+
+    while let Some(Reverse((cost, system_id))) = candidates.pop() {
+        if !visited.insert(system_id) {
+            continue;
+        }
+        let Some(node) = topology.node(system_id, tie_direction) else {
+            continue;
+        };
+        let next_hops = paths[&system_id].1.clone();
+
+        for (&neighbor_id, neighbor) in &node.neighbors {
+            let Ok(neighbor_id) = SystemID::try_from(neighbor_id) else {
+                continue;
+            };
+            if visited.contains(&neighbor_id)
+                || !is_allowed(system_id, node.level, neighbor.level)
+                || !topology.is_bidirectional(
+                    (system_id, node.level),
+                    (neighbor_id, neighbor.level),
+                    backlink_direction,
+                )
+            {
+                continue;
+            }
+
+            let neighbor_cost =
+                cost.saturating_add(neighbor.cost.unwrap_or(common::DEFAULT_DISTANCE));
+            // The next hops of the root's direct neighbors are the links to those neighbors. Every
+            // other node inherits the next hops of the node it was reached through.
+            let neighbor_next_hops = if system_id == root {
+                let weight = link_weight(neighbor);
+                neighbor
+                    .link_ids
+                    .iter()
+                    .flatten()
+                    .map(|link_id| NextHop {
+                        system_id: neighbor_id,
+                        link_id: link_id.local_id,
+                        weight,
+                    })
+                    .collect()
+            } else {
+                next_hops.clone()
+            };
+
+            match paths.entry(neighbor_id) {
+                Entry::Vacant(entry) => {
+                    entry.insert((neighbor_cost, neighbor_next_hops));
+                    candidates.push(Reverse((neighbor_cost, neighbor_id)));
+                }
+                Entry::Occupied(mut entry) => {
+                    let (best_cost, best_next_hops) = entry.get_mut();
+                    if neighbor_cost < *best_cost {
+                        *best_cost = neighbor_cost;
+                        *best_next_hops = neighbor_next_hops;
+                        candidates.push(Reverse((neighbor_cost, neighbor_id)));
+                    } else if neighbor_cost == *best_cost {
+                        best_next_hops.extend(neighbor_next_hops);
+                    }
+                }
+            }
+        }
+    }
+
+    // Prefixes advertised by the nodes that were reached are routed via the paths to those nodes.
+    let prefix_direction = match direction {
+        SpfDirection::North => TieDirection::South,
+        SpfDirection::South => TieDirection::North,
+    };
+    let mut routes: BTreeMap<IPPrefixType, Route> = BTreeMap::new();
+    for &(originator, tie_direction, element) in &topology.prefixes {
+        if originator == root || tie_direction != prefix_direction {
+            continue;
+        }
+        let Some((cost, next_hops)) = paths.get(&originator) else {
+            continue;
+        };
+        for (prefix, attributes) in &element.prefixes {
+            let metric = cost.saturating_add(attributes.metric);
+            routes
+                .entry(prefix.clone())
+                .and_modify(|route| route.add_path(metric, next_hops))
+                .or_insert_with(|| Route {
+                    metric,
+                    next_hops: next_hops.clone(),
+                });
+        }
+    }
+    routes
+}
+
+/// The weight of each link to `neighbor` for bandwidth-weighted ECMP. The bandwidth of a neighbor
+/// in a Node TIE is the sum over all parallel links to it, so it is split evenly between the links.
+fn link_weight(neighbor: &NodeNeighborsTIEElement) -> Option<u32> {
+    let bandwidth = u32::try_from(neighbor.bandwidth?).ok()?;
+    let links = neighbor
+        .link_ids
+        .as_ref()
+        .map_or(1, |link_ids| link_ids.len().max(1));
+    Some((bandwidth / u32::try_from(links).ok()?).max(1))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        net::{IpAddr, Ipv4Addr},
+    };
+
+    use crate::{
+        models::{common, encoding},
+        tie_exchange::LinkStateDatabase,
+        wrapper::{
+            self, SystemID, TIEHeader, TIEPacket, TIESubtype, TieDirection, TieNumber, TIEID,
+        },
+    };
+
+    use super::{
+        apply_negative_disaggregation, compute_routes, positive_disaggregation, same_plane_tofs,
+        tof_negative_disaggregation, NextHop, SpfDirection,
+    };
+
+    const LIFETIME: u32 = common::DEFAULT_LIFETIME as u32;
+
+    fn system_id(id: i64) -> SystemID {
+        SystemID::try_from(id).unwrap()
+    }
+
+    /// A next hop without a weight, as computed for neighbors which advertise no bandwidth.
+    fn next_hop(id: i64, link_id: common::LinkIDType) -> NextHop {
+        NextHop {
+            system_id: system_id(id),
+            link_id,
+            weight: None,
+        }
+    }
+
+    fn insert(
+        ls_db: &mut LinkStateDatabase,
+        originator: i64,
+        direction: TieDirection,
+        element: encoding::TIEElement,
+    ) {
+        let tie_type = match element {
+            encoding::TIEElement::Node(_) => TIESubtype::Node,
+            encoding::TIEElement::PositiveDisaggregationPrefixes(_) => {
+                TIESubtype::PositiveDisaggregationPrefix
+            }
+            encoding::TIEElement::NegativeDisaggregationPrefixes(_) => {
+                TIESubtype::NegativeDisaggregationPrefix
+            }
+            _ => TIESubtype::Prefix,
+        };
+        let header = TIEHeader {
+            tie_id: TIEID {
+                direction,
+                originator: system_id(originator),
+                tie_type,
+                tie_nr: TieNumber::FIRST,
+            },
+            seq_nr: 1,
+            origination_time: None,
+            origination_lifetime: None,
+        };
+        ls_db.insert(&TIEPacket { header, element }, LIFETIME);
+    }
+
+    /// Insert North and South Node TIEs for the given node. `neighbors` is a list of
+    /// (system id, level, link id) tuples.
+    fn insert_node(
+        ls_db: &mut LinkStateDatabase,
+        originator: i64,
+        level: i8,
+        neighbors: &[(i64, i8, i32)],
+    ) {
+        let neighbors = neighbors
+            .iter()
+            .map(|&(system_id, level, link_id)| {
+                let link_ids = BTreeSet::from([encoding::LinkIDPair::new(
+                    link_id, link_id, None, None, None, None, None,
+                )]);
+                let neighbor = encoding::NodeNeighborsTIEElement::new(
+                    level,
+                    common::DEFAULT_DISTANCE,
+                    link_ids,
+                    None,
+                );
+                (system_id, neighbor)
+            })
+            .collect();
+        let element = wrapper::node_element(level, neighbors);
+        for direction in [TieDirection::North, TieDirection::South] {
+            insert(
+                ls_db,
+                originator,
+                direction,
+                encoding::TIEElement::Node(element.clone()),
+            );
+        }
+    }
+
+    fn insert_prefix(
+        ls_db: &mut LinkStateDatabase,
+        originator: i64,
+        direction: TieDirection,
+        prefix: common::IPPrefixType,
+    ) {
+        let attributes = encoding::PrefixAttributes::new(1, None, None, None, None, None, None);
+        let element = encoding::PrefixTIEElement::new(BTreeMap::from([(prefix, attributes)]));
+        insert(
+            ls_db,
+            originator,
+            direction,
+            encoding::TIEElement::Prefixes(element),
+        );
+    }
+
+    fn leaf_prefix() -> common::IPPrefixType {
+        wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), 24)
+    }
+
+    fn default_prefix() -> common::IPPrefixType {
+        wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+    }
+
+    /// A leaf (1) at level 0 connected to two spines (2 and 3) at level 1. The spines advertise
+    /// default routes southbound and the leaf advertises a prefix northbound.
+    fn fabric() -> LinkStateDatabase {
+        let mut ls_db = LinkStateDatabase::new();
+        insert_node(&mut ls_db, 1, 0, &[(2, 1, 0), (3, 1, 1)]);
+        insert_node(&mut ls_db, 2, 1, &[(1, 0, 0)]);
+        insert_node(&mut ls_db, 3, 1, &[(1, 0, 0)]);
+        insert_prefix(&mut ls_db, 1, TieDirection::North, leaf_prefix());
+        insert_prefix(&mut ls_db, 2, TieDirection::South, default_prefix());
+        insert_prefix(&mut ls_db, 3, TieDirection::South, default_prefix());
+        ls_db
+    }
+
+    #[test]
+    fn test_north_spf_ecmp() {
+        let ls_db = fabric();
+        let routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        let route = &routes[&default_prefix()];
+        assert_eq!(route.metric, common::DEFAULT_DISTANCE + 1);
+        assert_eq!(
+            route.next_hops,
+            BTreeSet::from([next_hop(2, 0), next_hop(3, 1),])
+        );
+        // The leaf's own prefix is not routed, and the S-SPF has nowhere to go.
+        assert_eq!(routes.len(), 1);
+        assert!(compute_routes(&ls_db, system_id(1), SpfDirection::South).is_empty());
+    }
+
+    #[test]
+    fn test_south_spf() {
+        let ls_db = fabric();
+        let routes = compute_routes(&ls_db, system_id(2), SpfDirection::South);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(
+            routes[&leaf_prefix()].next_hops,
+            BTreeSet::from([next_hop(1, 0)])
+        );
+    }
+
+    #[test]
+    fn test_next_hop_weights() {
+        // Leaf 1 has two parallel links to spine 2 and a single link to spine 3. The bandwidth it
+        // advertises for spine 2 is the total over both links.
+        let mut ls_db = fabric();
+        let neighbor = |link_ids: &[i32], bandwidth| {
+            let link_ids = link_ids
+                .iter()
+                .map(|&link_id| {
+                    encoding::LinkIDPair::new(link_id, link_id, None, None, None, None, None)
+                })
+                .collect::<BTreeSet<_>>();
+            encoding::NodeNeighborsTIEElement::new(
+                1,
+                common::DEFAULT_DISTANCE,
+                link_ids,
+                Some(bandwidth),
+            )
+        };
+        let neighbors = BTreeMap::from([(2, neighbor(&[0, 2], 400)), (3, neighbor(&[1], 100))]);
+        let element = wrapper::node_element(0, neighbors);
+        for direction in [TieDirection::North, TieDirection::South] {
+            insert(
+                &mut ls_db,
+                1,
+                direction,
+                encoding::TIEElement::Node(element.clone()),
+            );
+        }
+
+        let routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        let weights = routes[&default_prefix()]
+            .next_hops
+            .iter()
+            .map(|next_hop| (next_hop.link_id, next_hop.weight))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            weights,
+            vec![(0, Some(200)), (2, Some(200)), (1, Some(100))]
+        );
+    }
+
+    #[test]
+    fn test_south_spf_east_west_leaves() {
+        // Leaf 4 is connected to leaf 1 East-West, and advertises its own prefix.
+        let mut ls_db = fabric();
+        let other_leaf_prefix = wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::new(10, 0, 4, 0)), 24);
+        insert_node(&mut ls_db, 1, 0, &[(2, 1, 0), (3, 1, 1), (4, 0, 2)]);
+        insert_node(&mut ls_db, 4, 0, &[(1, 0, 0)]);
+        insert_prefix(
+            &mut ls_db,
+            4,
+            TieDirection::North,
+            other_leaf_prefix.clone(),
+        );
+
+        // Leaf 1 routes directly to leaf 4's prefix, but has nothing to disaggregate.
+        let routes = compute_routes(&ls_db, system_id(1), SpfDirection::South);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(
+            routes[&other_leaf_prefix].next_hops,
+            BTreeSet::from([next_hop(4, 2)])
+        );
+        assert!(positive_disaggregation(&ls_db, system_id(1), &routes).is_empty());
+    }
+
+    #[test]
+    fn test_spf_requires_backlink() {
+        let mut ls_db = fabric();
+        // Spine 3 no longer lists the leaf as a neighbor, so the leaf must not route through it.
+        insert_node(&mut ls_db, 3, 1, &[]);
+        let routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        assert_eq!(
+            routes[&default_prefix()].next_hops,
+            BTreeSet::from([next_hop(2, 0)])
+        );
+    }
+
+    #[test]
+    fn test_positive_disaggregation() {
+        // Add a second leaf (4) with its own prefix, which is only connected to spine 2.
+        let mut ls_db = fabric();
+        let other_leaf_prefix = wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::new(10, 0, 4, 0)), 24);
+        insert_node(&mut ls_db, 4, 0, &[(2, 1, 0)]);
+        insert_node(&mut ls_db, 2, 1, &[(1, 0, 0), (4, 0, 1)]);
+        insert_prefix(
+            &mut ls_db,
+            4,
+            TieDirection::North,
+            other_leaf_prefix.clone(),
+        );
+
+        // Spine 3 can't reach leaf 4, so spine 2 disaggregates its prefix. Spine 3 has nothing to
+        // disaggregate, since spine 2 can reach everything spine 3 can.
+        let south_routes = compute_routes(&ls_db, system_id(2), SpfDirection::South);
+        let disaggregated = positive_disaggregation(&ls_db, system_id(2), &south_routes);
+        assert_eq!(
+            disaggregated.keys().collect::<Vec<_>>(),
+            vec![&other_leaf_prefix]
+        );
+        let south_routes = compute_routes(&ls_db, system_id(3), SpfDirection::South);
+        assert!(positive_disaggregation(&ls_db, system_id(3), &south_routes).is_empty());
+
+        // Leaf 1 then routes the disaggregated prefix via spine 2 only.
+        let element = encoding::PrefixTIEElement::new(disaggregated);
+        insert(
+            &mut ls_db,
+            2,
+            TieDirection::South,
+            encoding::TIEElement::PositiveDisaggregationPrefixes(element),
+        );
+        let routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        assert_eq!(routes[&default_prefix()].next_hops.len(), 2);
+        assert_eq!(
+            routes[&other_leaf_prefix].next_hops,
+            BTreeSet::from([next_hop(2, 0)])
+        );
+    }
+
+    #[test]
+    fn test_positive_disaggregation_ignores_unrelated_peers() {
+        // Spine 5 is on the same level as spine 2, but only connects to leaf 6. It can't reach
+        // leaf 1, but shares no southbound neighbor with spine 2, so nothing is disaggregated.
+        let mut ls_db = fabric();
+        insert_node(&mut ls_db, 5, 1, &[(6, 0, 0)]);
+        insert_node(&mut ls_db, 6, 0, &[(5, 1, 0)]);
+
+        let south_routes = compute_routes(&ls_db, system_id(2), SpfDirection::South);
+        assert!(south_routes.contains_key(&leaf_prefix()));
+        assert!(positive_disaggregation(&ls_db, system_id(2), &south_routes).is_empty());
+    }
+
+    #[test]
+    fn test_apply_negative_disaggregation() {
+        let mut ls_db = fabric();
+        let negative = |prefix: common::IPPrefixType| {
+            let attributes = encoding::PrefixAttributes::new(1, None, None, None, None, None, None);
+            let element = encoding::PrefixTIEElement::new(BTreeMap::from([(prefix, attributes)]));
+            encoding::TIEElement::NegativeDisaggregationPrefixes(element)
+        };
+
+        // Spine 2 can't reach the prefix, so the leaf only uses spine 3 for it.
+        insert(&mut ls_db, 2, TieDirection::South, negative(leaf_prefix()));
+        let mut routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        let disaggregated = apply_negative_disaggregation(&ls_db, system_id(1), &mut routes);
+        assert!(disaggregated.is_empty());
+        assert_eq!(
+            routes[&leaf_prefix()].next_hops,
+            BTreeSet::from([next_hop(3, 1)])
+        );
+
+        // Neither spine can reach the prefix, so it is unreachable and disaggregated further.
+        insert(&mut ls_db, 3, TieDirection::South, negative(leaf_prefix()));
+        let mut routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        let disaggregated = apply_negative_disaggregation(&ls_db, system_id(1), &mut routes);
+        assert_eq!(
+            disaggregated.keys().collect::<Vec<_>>(),
+            vec![&leaf_prefix()]
+        );
+        assert!(routes[&leaf_prefix()].next_hops.is_empty());
+
+        // Only northbound neighbors are taken into account, so leaf 4 can't punch holes.
+        let mut ls_db = fabric();
+        insert(&mut ls_db, 4, TieDirection::South, negative(leaf_prefix()));
+        let mut routes = compute_routes(&ls_db, system_id(1), SpfDirection::North);
+        assert!(apply_negative_disaggregation(&ls_db, system_id(1), &mut routes).is_empty());
+        assert!(!routes.contains_key(&leaf_prefix()));
+    }
+
+    #[test]
+    fn test_tof_negative_disaggregation() {
+        // Plane A has ToFs 20 and 21 above spine 2, plane B has ToF 30 above spine 3. Leaf 1 is
+        // connected to both spines, but leaf 4 is only connected to spine 3.
+        let tof = common::TOP_OF_FABRIC_LEVEL;
+        let mut ls_db = LinkStateDatabase::new();
+        let other_leaf_prefix = wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::new(10, 0, 4, 0)), 24);
+        insert_node(&mut ls_db, 1, 0, &[(2, 1, 0), (3, 1, 1)]);
+        insert_node(&mut ls_db, 4, 0, &[(3, 1, 0)]);
+        insert_node(&mut ls_db, 2, 1, &[(1, 0, 0), (20, tof, 1), (21, tof, 2)]);
+        insert_node(&mut ls_db, 3, 1, &[(1, 0, 0), (4, 0, 1), (30, tof, 2)]);
+        insert_node(&mut ls_db, 20, tof, &[(2, 1, 0)]);
+        insert_node(&mut ls_db, 21, tof, &[(2, 1, 0)]);
+        insert_node(&mut ls_db, 30, tof, &[(3, 1, 0)]);
+        insert_prefix(&mut ls_db, 1, TieDirection::North, leaf_prefix());
+        insert_prefix(
+            &mut ls_db,
+            4,
+            TieDirection::North,
+            other_leaf_prefix.clone(),
+        );
+
+        let plane = same_plane_tofs(&ls_db, system_id(20));
+        assert_eq!(plane, BTreeSet::from([system_id(21)]));
+        let disaggregated = tof_negative_disaggregation(&ls_db, system_id(20), &plane);
+        assert_eq!(
+            disaggregated.keys().collect::<Vec<_>>(),
+            vec![&other_leaf_prefix]
+        );
+
+        let plane = same_plane_tofs(&ls_db, system_id(30));
+        assert!(plane.is_empty());
+        assert!(tof_negative_disaggregation(&ls_db, system_id(30), &plane).is_empty());
+    }
+
+    #[test]
+    fn test_tof_negative_disaggregation_advertised_plane() {
+        // ToFs 20, 21 and 22 are all in plane A, but ToF 22 shares no spine with ToF 20: 21 is above
+        // spines 2 and 5, while 22 is above spines 5 and 7. Leaf 6 is only below spine 7.
+        let tof = common::TOP_OF_FABRIC_LEVEL;
+        let mut ls_db = LinkStateDatabase::new();
+        let other_leaf_prefix = wrapper::ip_prefix(IpAddr::V4(Ipv4Addr::new(10, 0, 6, 0)), 24);
+        insert_node(&mut ls_db, 1, 0, &[(2, 1, 0), (3, 1, 1)]);
+        insert_node(&mut ls_db, 6, 0, &[(7, 1, 0)]);
+        insert_node(&mut ls_db, 2, 1, &[(1, 0, 0), (20, tof, 1), (21, tof, 2)]);
+        insert_node(&mut ls_db, 3, 1, &[(1, 0, 0), (30, tof, 1)]);
+        insert_node(&mut ls_db, 5, 1, &[(21, tof, 0), (22, tof, 1)]);
+        insert_node(&mut ls_db, 7, 1, &[(6, 0, 0), (22, tof, 1)]);
+        insert_node(&mut ls_db, 20, tof, &[(2, 1, 0)]);
+        insert_node(&mut ls_db, 21, tof, &[(2, 1, 0), (5, 1, 1)]);
+        insert_node(&mut ls_db, 22, tof, &[(5, 1, 0), (7, 1, 1)]);
+        insert_node(&mut ls_db, 30, tof, &[(3, 1, 0)]);
+        insert_prefix(
+            &mut ls_db,
+            6,
+            TieDirection::North,
+            other_leaf_prefix.clone(),
+        );
+
+        // Without any advertised planes, ToF 20 takes ToF 22 for a ToF in another plane.
+        let plane = same_plane_tofs(&ls_db, system_id(20));
+        assert_eq!(plane, BTreeSet::from([system_id(21)]));
+        assert_eq!(
+            tof_negative_disaggregation(&ls_db, system_id(20), &plane)
+                .keys()
+                .collect::<Vec<_>>(),
+            vec![&other_leaf_prefix]
+        );
+
+        // Once ToF 21 advertises the ToFs it sees in its plane, ToF 20 knows leaf 6 is reachable.
+        let mut element = wrapper::node_element(tof, BTreeMap::new());
+        element.same_plane_tofs = Some(
+            same_plane_tofs(&ls_db, system_id(21))
+                .into_iter()
+                .map(|tof| tof.get())
+                .collect(),
+        );
+        insert(
+            &mut ls_db,
+            21,
+            TieDirection::North,
+            encoding::TIEElement::Node(element),
+        );
+        assert!(tof_negative_disaggregation(&ls_db, system_id(20), &plane).is_empty());
+    }
+}
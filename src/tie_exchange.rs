@@ -1,20 +1,26 @@
 use std::{
+    cmp::{Ordering, Reverse},
     collections::{btree_map::Entry, BTreeMap, BTreeSet},
     error::Error,
-    ops::Bound,
-    time::{Duration, SystemTime},
+    ops::{Bound, RangeBounds},
+    time::{Duration, Instant},
 };
 
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
 use crate::{
     lie_exchange::Neighbor,
-    models::encoding,
+    models::{common, encoding},
     wrapper::{
-        SystemID, TIDEPacket, TIEHeader, TIEHeaderWithLifetime, TIEPacket, TIESubtype, TIREPacket,
-        TieDirection, TIEID, TOP_OF_FABRIC_LEVEL,
+        self, IEEE8021ASTimeStamp, LifetimeInSecs, SequenceNumber, SystemID, TIDEPacket, TIEHeader,
+        TIEHeaderWithLifetime, TIEPacket, TIESubtype, TIREPacket, TieDirection, TIEID,
+        TOP_OF_FABRIC_LEVEL,
     },
 };
 
-/// I don't know if this actually makes sense to have
+/// The per-adjacency flooding state (the "FloodState" structure of the spec). Note that the LSDB is
+/// not part of this structure--it is shared between all of the adjacencies on a node, and so is
+/// passed in to the methods which need it.
 pub struct TieStateMachine {
     /// Collection containing all the TIEs to transmit on the adjacency.
     transmit_ties: TIECollection,
@@ -24,21 +30,29 @@ pub struct TieStateMachine {
     requested_ties: TIECollection,
     /// Collection containing all TIEs that need retransmission with the according time to
     /// retransmit
-    retransmit_ties: TIECollection,
-    ls_db: LinkStateDatabase,
+    retransmit_ties: RetransmitQueue,
+    /// The southbound neighbors of this node which did not elect it as a flood repeater. North TIEs
+    /// originated by these neighbors are not reflooded northbound.
+    flood_reduced_originators: BTreeSet<SystemID>,
 }
 
 impl TieStateMachine {
-    pub fn new() -> TieStateMachine {
+    pub fn new(retransmit_config: RetransmitConfig) -> TieStateMachine {
         TieStateMachine {
             transmit_ties: TIECollection::new(),
             acknowledge_ties: TIECollection::new(),
             requested_ties: TIECollection::new(),
-            retransmit_ties: TIECollection::new(),
-            ls_db: LinkStateDatabase::new(),
+            retransmit_ties: RetransmitQueue::new(retransmit_config),
+            flood_reduced_originators: BTreeSet::new(),
         }
     }
 
+    /// Set the southbound neighbors which did not elect this node as a flood repeater (see
+    /// [flood_repeaters]).
+    pub fn set_flood_reduced_originators(&mut self, originators: BTreeSet<SystemID>) {
+        self.flood_reduced_originators = originators;
+    }
+
     /// Implements section 4.2.3.3.1.2.1 TIDE Generation
     /// 4.2.3.3.1.2.1. TIDE Generation
     /// As given by timer constant, periodically generate TIDEs by:
@@ -59,71 +73,74 @@ impl TieStateMachine {
     /// implementation to limit the amount of TIE headers per TIDE so the sent TIDE PDU does not
     /// exceed interface MTU.
     /// TIDE PDUs SHOULD be spaced on sending to prevent packet drops
-    pub fn generate_tide(&mut self, tirdes_per_pkt: usize) -> Vec<TIDEPacket> {
-        fn positive_lifetime(header: &TIEHeader) -> Result<bool, Box<dyn Error>> {
-            let origination_time = header.origination_time;
-            let lifetime_in_secs = header.origination_lifetime;
-            match (origination_time, lifetime_in_secs) {
-                (Some(origination_time), Some(lifetime_in_secs)) => {
-                    let origination_time: SystemTime = origination_time.try_into()?;
-                    let lifetime = Duration::from_secs(lifetime_in_secs as u64);
-                    let elapsed = origination_time.elapsed()?;
-                    Ok(elapsed < lifetime)
-                }
-                _ => {
-                    tracing::warn!(timestamp =? origination_time,
-                        lifetime =? lifetime_in_secs,
-                        "Timestamp or lifetime missing");
-                    Ok(false)
-                }
-            }
-        }
-
+    pub fn generate_tide(
+        &mut self,
+        link_info: &LinkInfo,
+        ls_db: &LinkStateDatabase,
+        tirdes_per_pkt: usize,
+    ) -> Vec<TIDEPacket> {
         // TODO: Interpreting "TIEDB" as "LSDB".
         // 2. HEADERS = At most TIRDEs_PER_PKT headers in TIEDB starting at NEXT_TIDE_ID or
         //    higher that SHOULD be filtered by is_tide_entry_filtered and MUST either have a
         //    lifetime left > 0 or have no content
-        let mut headers = self
-            .ls_db
-            .ties
+        let mut headers = ls_db
             .iter()
-            .filter(|(_, tie)| self.is_tide_entry_filtered(tie))
-            .filter(|(_, tie)| {
-                let positive_lifetime = match positive_lifetime(&tie.header) {
-                    Ok(x) => x,
-                    Err(err) => {
-                        tracing::warn!(err = err, "couldn't check lifetime");
-                        false
-                    }
-                };
+            .filter(|tie| !self.is_tide_entry_filtered(link_info, ls_db, &tie.header()))
+            .filter(|tie| {
+                let positive_lifetime = ls_db.remaining_lifetime(&tie.header().tie_id) > 0;
                 positive_lifetime || !tie_has_content(tie)
             })
             .collect::<Vec<_>>();
         // Sorting done here so that "first element" and "last element" hopefully correspond to
         // to smallest and largest elements of the vector.
-        headers.sort_by_key(|a| a.0);
+        headers.sort_by_key(|a| a.header());
 
-        let tides = headers
-            .chunks(tirdes_per_pkt)
-            .map(|headers| {
+        // If there are no headers at all, we still need to send a single empty TIDE covering the
+        // entire range, so that the neighbor knows we have nothing.
+        let mut chunks = headers.chunks(tirdes_per_pkt).collect::<Vec<_>>();
+        if chunks.is_empty() {
+            chunks.push(&[]);
+        }
+
+        // NOTE: The first TIDE always starts at MIN_TIEID and each following TIDE starts where the
+        // previous one ended, so that the TIDEs cover the entire TIEID space without gaps.
+        // Likewise, the last TIDE always ends at MAX_TIEID, even if it happens to be full.
+        let mut next_tide_id = None;
+        let last_chunk = chunks.len() - 1;
+        let tides = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, headers)| {
                 // 3. if HEADERS is empty then START = MIN_TIEID else START = first element in HEADERS
-                let start_range = headers.first().map(|x| *x.0);
+                let start_range = next_tide_id;
 
                 // 4. if HEADERS' size less than TIRDEs_PER_PKT then END = MAX_TIEID else END = last
                 //     element in HEADERS
-                let end_range = if headers.len() < tirdes_per_pkt {
+                let end_range = if headers.len() < tirdes_per_pkt || i == last_chunk {
                     None
                 } else {
-                    headers.last().map(|x| *x.0)
+                    headers.last().map(|x| x.header().tie_id)
                 };
+                // 6. NEXT_TIDE_ID = END
+                next_tide_id = end_range;
 
                 // 5. send *sorted* HEADERS as TIDE setting START and END as its range
                 TIDEPacket {
                     start_range,
                     end_range,
+                    // Remaining lifetimes are rounded down so that the TIDE doesn't need to change
+                    // every second.
                     headers: headers
                         .iter()
-                        .map(|(_, tie)| TIEHeaderWithLifetime::new(tie.header))
+                        .map(|tie| {
+                            let header = tie.header();
+                            let lifetime = ls_db.remaining_lifetime(&header.tie_id);
+                            let interval = common::ROUNDDOWN_LIFETIME_INTERVAL as LifetimeInSecs;
+                            TIEHeaderWithLifetime {
+                                header,
+                                remaining_lifetime: lifetime - lifetime % interval,
+                            }
+                        })
                         .collect(),
                 }
             })
@@ -166,6 +183,7 @@ impl TieStateMachine {
     pub fn process_tide(
         &mut self,
         link_info: &LinkInfo,
+        ls_db: &mut LinkStateDatabase,
         from_northbound: bool,
         tide: &TIDEPacket,
     ) -> Result<(), Box<dyn Error>> {
@@ -181,7 +199,7 @@ impl TieStateMachine {
             let is_originator = link_info.local_system_id == tide_header.header.tie_id.originator;
 
             // 1. DBTIE = find HEADER in current LSDB
-            let db_tie = self.ls_db.find(&tide_header.header);
+            let db_tie = ls_db.header_with_lifetime(&tide_header.header.tie_id);
 
             // 2. if HEADER < LASTPROCESSED then report error and reset adjacency and return
             if Some(tide_header.header.tie_id) < last_processed {
@@ -198,7 +216,7 @@ impl TieStateMachine {
                     Bound::Excluded(tide_header.header.tie_id),
                 ),
             };
-            let range = self.ls_db.ties.range(range).map(|x| x.1.header);
+            let range = ls_db.range(range).map(|x| x.header());
             tx_keys.extend(range);
 
             // 4. LASTPROCESSED = HEADER
@@ -209,44 +227,44 @@ impl TieStateMachine {
                     // 5. if DBTIE not found then
                     if is_originator {
                         // I) if originator is this node then bump_own_tie
-                        self.bump_own_tie(&tide_header.header)
+                        self.bump_own_tie(link_info, ls_db, &tide_header.header)
                     } else {
                         // II) else put HEADER into REQKEYS
                         req_keys.push(tide_header.header);
                     }
                 }
-                Some(db_tie) => {
+                Some(db_header) => match db_header.compare(tide_header) {
                     // 6. if DBTIE.HEADER < HEADER then
-                    if db_tie.header < tide_header.header {
+                    Ordering::Less => {
                         if is_originator {
                             // I) if originator is this node then bump_own_tie else
-                            self.bump_own_tie(&tide_header.header);
+                            self.bump_own_tie(link_info, ls_db, &tide_header.header);
                         } else {
                             // i. if this is a North TIE header from a northbound neighbor then
                             //    override DBTIE in LSDB with HEADER
                             if tide_header.header.tie_id.direction == TieDirection::North
                                 && from_northbound
                             {
-                                self.ls_db.replace(&db_tie, &tide_header.header);
+                                ls_db.replace(tide_header);
                             } else {
                                 // ii. else put HEADER into REQKEYS
                                 req_keys.push(tide_header.header);
                             }
                         }
-                    } else if db_tie.header > tide_header.header {
-                        // 7. if DBTIE.HEADER > HEADER then put DBTIE.HEADER into TXKEYS
-                        tx_keys.push(db_tie.header)
-                    } else {
-                        // 8. if DBTIE.HEADER = HEADER then
-                        if tie_has_content(&db_tie) {
+                    }
+                    // 7. if DBTIE.HEADER > HEADER then put DBTIE.HEADER into TXKEYS
+                    Ordering::Greater => tx_keys.push(db_header.header),
+                    // 8. if DBTIE.HEADER = HEADER then
+                    Ordering::Equal => {
+                        if ls_db.has_content(&db_header.header.tie_id) {
                             // I) if DBTIE has content already then put DBTIE.HEADER into CLEARKEYS
-                            clear_keys.push(db_tie.header);
+                            clear_keys.push(db_header.header);
                         } else {
                             // II) else put HEADER into REQKEYS
                             req_keys.push(tide_header.header);
                         }
                     }
-                }
+                },
             }
         }
 
@@ -258,18 +276,18 @@ impl TieStateMachine {
             (Some(start), None) => (Bound::Excluded(start), Bound::Unbounded),
             (Some(start), Some(end)) => (Bound::Excluded(start), Bound::Included(end)),
         };
-        for (_, tie) in self.ls_db.ties.range(range) {
-            tx_keys.push(tie.header);
+        for tie in ls_db.range(range) {
+            tx_keys.push(tie.header());
         }
 
         // d. for all TIEs in TXKEYS try_to_transmit_tie(TIE)
         for tie in tx_keys {
-            self.try_to_transmit_tie(link_info, tie);
+            self.try_to_transmit_tie(link_info, ls_db, tie);
         }
 
         // e. for all TIEs in REQKEYS request_tie(TIE)
         for tie in req_keys {
-            self.request_tie(tie);
+            self.request_tie(link_info, tie);
         }
 
         // f. for all TIEs in CLEARKEYS remove_from_all_queues(TIE)
@@ -284,22 +302,43 @@ impl TieStateMachine {
     /// as TIREs. When sending TIREs with elements from TIES_REQ the `remaining_lifetime` field in
     /// `TIEHeaderWithLifeTime` MUST be set to 0 to force reflooding from the neighbor even if the
     /// TIEs seem to be same.
-    pub fn generate_tire(&mut self) -> TIREPacket {
+    /// Note that TIES_ACK is drained, so that every acknowledgement is only sent once. TIES_REQ is
+    /// kept until the requested TIEs arrive, and is only included if `with_requests` is set. This
+    /// should be done periodically, so that requests which were lost are eventually sent again.
+    /// The headers are split across as many TIREs as needed so that none of them holds more than
+    /// `tirdes_per_pkt` headers. If there is nothing to send, no TIREs are returned.
+    pub fn generate_tire(
+        &mut self,
+        ls_db: &LinkStateDatabase,
+        tirdes_per_pkt: usize,
+        with_requests: bool,
+    ) -> Vec<TIREPacket> {
         let mut headers = BTreeSet::new();
-        for &header in self.requested_ties.iter() {
+        if with_requests {
+            for &header in self.requested_ties.iter() {
+                let header = TIEHeaderWithLifetime {
+                    header,
+                    remaining_lifetime: 0,
+                };
+                headers.insert(header);
+            }
+        }
+
+        for header in self.acknowledge_ties.take_all() {
             let header = TIEHeaderWithLifetime {
                 header,
-                remaining_lifetime: 0,
+                remaining_lifetime: ls_db.remaining_lifetime(&header.tie_id),
             };
             headers.insert(header);
         }
 
-        for &header in self.acknowledge_ties.iter() {
-            let header = TIEHeaderWithLifetime::new(header);
-            headers.insert(header);
-        }
-
-        TIREPacket { headers }
+        let headers = headers.into_iter().collect::<Vec<_>>();
+        headers
+            .chunks(tirdes_per_pkt)
+            .map(|headers| TIREPacket {
+                headers: headers.iter().copied().collect(),
+            })
+            .collect()
     }
 
     /// 4.2.3.3.1.3.2. TIRE Processing
@@ -317,37 +356,40 @@ impl TieStateMachine {
     /// b. for all TIEs in TXKEYS try_to_transmit_tie(TIE)
     /// c. for all TIEs in REQKEYS request_tie(TIE)
     /// d. for all TIEs in ACKKEYS tie_been_acked(TIE)
-    pub fn process_tire(&mut self, link_info: &LinkInfo, tire: &TIREPacket) {
+    pub fn process_tire(
+        &mut self,
+        link_info: &LinkInfo,
+        ls_db: &LinkStateDatabase,
+        tire: &TIREPacket,
+    ) {
         let mut req_keys = vec![];
         let mut tx_keys = vec![];
         let mut ack_keys = vec![];
         // a. for every HEADER in TIRE do
         for tire_header in &tire.headers {
             // 1. DBTIE = find HEADER in current LSDB
-            let db_tie = self.ls_db.find(&tire_header.header);
+            let db_tie = ls_db.header_with_lifetime(&tire_header.header.tie_id);
             // 2. if DBTIE not found then do nothing
-            if let Some(db_tie) = db_tie {
-                if db_tie.header < tire_header.header {
+            if let Some(db_header) = db_tie {
+                match db_header.compare(tire_header) {
                     // 3. if DBTIE.HEADER < HEADER then put HEADER into REQKEYS
-                    req_keys.push(tire_header.header);
-                } else if db_tie.header > tire_header.header {
+                    Ordering::Less => req_keys.push(tire_header.header),
                     // 4. if DBTIE.HEADER > HEADER then put DBTIE.HEADER into TXKEYS
-                    tx_keys.push(db_tie.header);
-                } else {
+                    Ordering::Greater => tx_keys.push(db_header.header),
                     // 5. if DBTIE.HEADER = HEADER then put DBTIE.HEADER into ACKKEYS
-                    ack_keys.push(db_tie.header);
+                    Ordering::Equal => ack_keys.push(db_header.header),
                 }
             }
         }
 
         // b. for all TIEs in TXKEYS try_to_transmit_tie(TIE)
         for tie in tx_keys {
-            self.try_to_transmit_tie(link_info, tie);
+            self.try_to_transmit_tie(link_info, ls_db, tie);
         }
 
         // c. for all TIEs in REQKEYS request_tie(TIE)
         for tie in req_keys {
-            self.request_tie(tie);
+            self.request_tie(link_info, tie);
         }
 
         // d. for all TIEs in ACKKEYS tie_been_acked(TIE)
@@ -377,12 +419,29 @@ impl TieStateMachine {
     ///         ii. else ACKTIE = DBTIE
     /// c. if TXTIE is set then try_to_transmit_tie(TXTIE)
     /// d. if ACKTIE is set then ack_tie(TIE)
-    pub fn process_tie(&mut self, link_info: &LinkInfo, tie: &TIEPacket) {
+    pub fn process_tie(
+        &mut self,
+        link_info: &LinkInfo,
+        ls_db: &mut LinkStateDatabase,
+        tie: &TIEPacket,
+        remaining_lifetime: LifetimeInSecs,
+    ) {
         let mut tx_tie = None;
         let mut ack_tie = None;
 
+        let tie_header = TIEHeaderWithLifetime {
+            header: tie.header,
+            remaining_lifetime,
+        };
+
         // a. DBTIE = find TIE in current LSDB
-        let db_tie = self.ls_db.find(&tie.header);
+        let db_tie = ls_db.find(&tie.header).map(|db_tie| {
+            let db_header = TIEHeaderWithLifetime {
+                header: db_tie.header(),
+                remaining_lifetime: ls_db.remaining_lifetime(&tie.header.tie_id),
+            };
+            (db_tie, db_header)
+        });
 
         // Convience closure--this implements the following:
         // 1. if originator is this node then bump_own_tie with a short remaining lifetime
@@ -390,11 +449,11 @@ impl TieStateMachine {
         let mut if_originator_then_bump_else_insert_tie_and_set_acktie = || {
             if tie.header.tie_id.originator == link_info.local_system_id {
                 // 1. if originator is this node then bump_own_tie with a short remaining lifetime
-                self.bump_own_tie(&tie.header);
+                self.bump_own_tie(link_info, ls_db, &tie.header);
             } else {
                 // 2. else insert TIE into LSDB and ACKTIE = TIE
-                self.ls_db.insert(tie);
-                ack_tie = Some(tie);
+                ls_db.insert_received(tie, remaining_lifetime, link_info.neighbor.system_id);
+                ack_tie = Some(tie.header);
             }
         };
 
@@ -406,62 +465,167 @@ impl TieStateMachine {
                 if_originator_then_bump_else_insert_tie_and_set_acktie();
             }
             // else
-            Some(db_tie) => {
-                if db_tie.header == tie.header {
+            Some((db_tie, db_header)) => match db_header.compare(&tie_header) {
+                Ordering::Equal => {
                     // 1. if DBTIE.HEADER = TIE.HEADER then
-                    if tie_has_content(&db_tie) {
+                    if tie_has_content(db_tie) {
                         // i. if DBTIE has content already then ACKTIE = TIE
-                        ack_tie = Some(tie);
+                        ack_tie = Some(tie.header);
                     } else {
                         // ii. else process like the "DBTIE.HEADER < TIE.HEADER" case
                         if_originator_then_bump_else_insert_tie_and_set_acktie();
                     }
-                } else if db_tie.header < tie.header {
+                }
+                Ordering::Less => {
                     // 2. if DBTIE.HEADER < TIE.HEADER then
                     // i. if originator is this node then bump_own_tie
                     // ii. else insert TIE into LSDB and ACKTIE = TIE
                     if_originator_then_bump_else_insert_tie_and_set_acktie();
-                } else {
+                }
+                Ordering::Greater => {
                     // 3. if DBTIE.HEADER > TIE.HEADER then
-                    if tie_has_content(&db_tie) {
+                    if tie_has_content(db_tie) {
                         // i. if DBTIE has content already then TXTIE = DBTIE
-                        tx_tie = Some(db_tie);
+                        tx_tie = Some(db_tie.header());
                     } else {
                         // ii. else ACKTIE = DBTIE
-                        ack_tie = Some(db_tie);
+                        ack_tie = Some(db_tie.header());
                     }
                 }
-            }
+            },
         }
         // c. if TXTIE is set then try_to_transmit_tie(TXTIE)
         if let Some(tie) = tx_tie {
-            self.try_to_transmit_tie(link_info, tie.header);
+            self.try_to_transmit_tie(link_info, ls_db, tie);
         }
 
         // d. if ACKTIE is set then ack_tie(TIE)
         if let Some(tie) = ack_tie {
-            self.ack_tie(tie.header);
+            self.ack_tie(tie);
         }
     }
 
-    pub fn send_ties(&mut self) {
-        todo!();
+    /// Drain TIES_TX, returning the TIEs which should be sent to the neighbor along with their
+    /// remaining lifetimes. The TIEs are taken from the LSDB, so if the LSDB contains a newer
+    /// version of a TIE than the one queued, the newer version is sent. TIEs which have no content
+    /// in the LSDB cannot be sent and are dropped from the queue. Sent TIEs are moved to TIES_RTX.
+    /// Additionally, any TIEs on TIES_RTX which have not been acknowledged by their retransmission
+    /// deadline are returned to be sent again.
+    pub fn send_ties(&mut self, ls_db: &LinkStateDatabase) -> Vec<(TIEPacket, LifetimeInSecs)> {
+        let mut ties = vec![];
+        for header in self.retransmit_ties.take_expired() {
+            match ls_db.find(&header) {
+                Some(DBTie::Content(tie)) => {
+                    tracing::debug!(tie_id =? header.tie_id, "retransmitting TIE");
+                    let lifetime = ls_db.remaining_lifetime(&header.tie_id);
+                    ties.push((tie, lifetime));
+                }
+                _ => {
+                    self.retransmit_ties.remove(&header.tie_id);
+                }
+            }
+        }
+
+        let queued = self.transmit_ties.iter().copied().collect::<Vec<_>>();
+        for header in queued {
+            match ls_db.find(&header) {
+                Some(DBTie::Content(tie)) => {
+                    let lifetime = ls_db.remaining_lifetime(&header.tie_id);
+                    self.move_to_rtx_list(tie.header);
+                    ties.push((tie, lifetime));
+                }
+                _ => {
+                    self.transmit_ties.remove(&header.tie_id);
+                }
+            }
+        }
+        ties
     }
 
-    /// Seemingly not used in the spec?
-    /// returns whether a TIE can be flood reduced or not
-    fn _is_flood_reduced(&self, _tie: &TIEPacket) -> bool {
-        todo!()
+    /// returns whether a TIE can be flood reduced or not (4.2.3.9). A TIE is flood reduced if it
+    /// would be flooded northbound, but it is a North TIE originated by a southbound neighbor which
+    /// did not elect this node as one of its flood repeaters. Some other node will reflood it instead.
+    fn is_flood_reduced(&self, link_info: &LinkInfo, tie: &TIEHeader) -> bool {
+        link_info.direction() == LinkDirection::North
+            && tie.tie_id.direction == TieDirection::North
+            && self
+                .flood_reduced_originators
+                .contains(&tie.tie_id.originator)
     }
 
     /// returns whether a header should be propagated in TIDE according to flooding scopes.
-    fn is_tide_entry_filtered(&self, tie: &TIEPacket) -> bool {
-        todo!()
+    /// if the value is true, then the header should be filtered (not included in TIDEs sent over
+    /// this link). This follows the "TIDE" row of the flooding scopes table in 4.2.3.4.
+    fn is_tide_entry_filtered(
+        &self,
+        link_info: &LinkInfo,
+        ls_db: &LinkStateDatabase,
+        tie: &TIEHeader,
+    ) -> bool {
+        let tie_id = tie.tie_id;
+        let this_level = link_info.local_level;
+        let this_system_id = link_info.local_system_id;
+        let neighbor_system_id = link_info.neighbor.system_id;
+        let self_originated = tie_id.originator == this_system_id;
+
+        let should_include = match link_info.direction() {
+            // include at least all non-self-originated North TIE headers and self-originated
+            // South TIE headers and Node South TIEs of nodes at same level
+            LinkDirection::South => match (tie_id.direction, tie_id.tie_type) {
+                (TieDirection::North, _) => !self_originated,
+                (TieDirection::South, TIESubtype::Node) => {
+                    self_originated || originator_level(ls_db, tie) == Some(this_level)
+                }
+                (TieDirection::South, _) => self_originated,
+            },
+            // include at least all Node South TIEs and all South TIEs originated by peer and all
+            // North TIEs
+            LinkDirection::North => match (tie_id.direction, tie_id.tie_type) {
+                (TieDirection::North, _) => true,
+                (TieDirection::South, TIESubtype::Node) => true,
+                (TieDirection::South, _) => tie_id.originator == neighbor_system_id,
+            },
+            // if this node is ToF then include all North TIEs, otherwise only self-originated TIEs
+            LinkDirection::EastWest => {
+                if this_level == TOP_OF_FABRIC_LEVEL {
+                    tie_id.direction == TieDirection::North
+                } else {
+                    self_originated
+                }
+            }
+        };
+        !should_include
     }
 
     /// returns whether a TIE request should be propagated to neighbor or not according to flooding scopes
-    fn is_request_filtered(&self, tie: &TIEHeader) -> bool {
-        todo!()
+    /// if the value is true, then the TIE should not be requested over this link. This follows the
+    /// "TIRE as Request" row of the flooding scopes table in 4.2.3.4.
+    fn is_request_filtered(&self, link_info: &LinkInfo, tie: &TIEHeader) -> bool {
+        let tie_id = tie.tie_id;
+        let neighbor_system_id = link_info.neighbor.system_id;
+
+        let south_scope = || {
+            // request all North TIEs and all peer's self-originated TIEs and all Node South TIEs
+            tie_id.direction == TieDirection::North
+                || tie_id.originator == neighbor_system_id
+                || (tie_id.direction == TieDirection::South && tie_id.tie_type == TIESubtype::Node)
+        };
+        // request all South TIEs
+        let north_scope = || tie_id.direction == TieDirection::South;
+
+        let should_request = match link_info.direction() {
+            LinkDirection::South => south_scope(),
+            LinkDirection::North => north_scope(),
+            // if this node is ToF then apply North scope rules, otherwise South scope rules
+            LinkDirection::EastWest => {
+                if link_info.local_level == TOP_OF_FABRIC_LEVEL {
+                    north_scope()
+                } else {
+                    south_scope()
+                }
+            }
+        };
+        !should_request
     }
 
     /// returns whether a TIE requested be flooded to neighbor or not according to flooding scopes.
@@ -469,23 +633,12 @@ impl TieStateMachine {
     /// `link_direction` indicates the direction that the link is in. In other words, this determines
     /// which direction the TIE would be flooded in (note that this is different from the TIE's direction,
     /// which indiates the direction the TIE just came from).
-    fn is_flood_filtered(&self, link_info: &LinkInfo, tie: &TIEHeader) -> bool {
-        // Implementation adapted from `rift-python`. Original `rift-python` documentation is follows:
-        // We cannot determine the level of the originator just by looking at the TIE header; we have
-        // to look in the TIE-DB to determine it. We can be confident the TIE is in the TIE-DB
-        // because we wouldn't be here, considering sending a TIE to a neighbor, if we did not have
-        // the TIE in the TIE-DB. Also, this question can only be asked about Node TIEs (other TIEs
-        //  don't store the level of the originator in the TIEPacket)
-        let get_originator_level = |tie: &TIEHeader| -> Option<u8> {
-            match self.ls_db.find(tie) {
-                Some(packet) => match packet.element {
-                    encoding::TIEElement::Node(node) => Some(node.level as u8),
-                    _ => None,
-                },
-                None => None,
-            }
-        };
-
+    fn is_flood_filtered(
+        &self,
+        link_info: &LinkInfo,
+        ls_db: &LinkStateDatabase,
+        tie: &TIEHeader,
+    ) -> bool {
         let tie_id = tie.tie_id;
         let this_level = link_info.local_level;
         let link_direction = link_info.direction();
@@ -493,11 +646,15 @@ impl TieStateMachine {
         let neighbor_system_id = link_info.neighbor.system_id;
 
         let should_flood = match (tie_id.direction, tie_id.tie_type) {
+            // The level of the originator is unknown if the TIE has been purged, in which case the
+            // TIE has no content to flood anyway.
             (TieDirection::South, TIESubtype::Node) => match link_direction {
                 // flood if level of originator is equal to this node
-                LinkDirection::South => get_originator_level(tie).unwrap() == this_level,
+                LinkDirection::South => originator_level(ls_db, tie) == Some(this_level),
                 // flood if level of originator is higher than this node
-                LinkDirection::North => get_originator_level(tie).unwrap() > this_level,
+                LinkDirection::North => {
+                    originator_level(ls_db, tie).is_some_and(|level| level > this_level)
+                }
                 // flood only if this node is not ToF
                 LinkDirection::EastWest => this_level != TOP_OF_FABRIC_LEVEL,
             },
@@ -514,10 +671,15 @@ impl TieStateMachine {
             (TieDirection::North, _) => match link_direction {
                 // never flood
                 LinkDirection::South => false,
-                // flood always
-                LinkDirection::North => true,
-                // flood only if this node is ToF
-                LinkDirection::EastWest => this_level == TOP_OF_FABRIC_LEVEL,
+                // flood always, unless this node is not a flood repeater for the originator
+                LinkDirection::North => !self.is_flood_reduced(link_info, tie),
+                // flood only if this node is ToF, or if this node is a leaf and the TIE is
+                // self-originated (4.3.9)
+                LinkDirection::EastWest => {
+                    this_level == TOP_OF_FABRIC_LEVEL
+                        || (this_level == common::LEAF_LEVEL as u8
+                            && tie_id.originator == this_system_id)
+                }
             },
         };
         !should_flood
@@ -529,18 +691,26 @@ impl TieStateMachine {
     ///      a. if TIE" is same or newer than TIE do nothing else
     ///      b. remove TIE" from TIES_ACK and add TIE to TIES_TX
     ///   3. else insert TIE into TIES_TX
-    fn try_to_transmit_tie(&mut self, link_info: &LinkInfo, tie: TIEHeader) {
-        if !self.is_flood_filtered(link_info, &tie) {
+    /// This is also used to reflood TIEs which were received on other adjacencies of the node.
+    /// NOTE: Besides TIES_RTX, the TIE is also removed from TIES_REQ. We are about to send our own
+    /// version of the TIE, so there is no point in still requesting it from the neighbor.
+    pub fn try_to_transmit_tie(
+        &mut self,
+        link_info: &LinkInfo,
+        ls_db: &LinkStateDatabase,
+        tie: TIEHeader,
+    ) {
+        if !self.is_flood_filtered(link_info, ls_db, &tie) {
+            self.retransmit_ties.remove(&tie.tie_id);
             self.requested_ties.remove(&tie.tie_id);
             if let Entry::Occupied(entry) = self.acknowledge_ties.entry(tie.tie_id) {
                 let other_tie = entry.get();
                 // a. if TIE" is same or newer than TIE do nothing else
                 // b. remove TIE" from TIES_ACK and add TIE to TIES_TX
-                if tie.seq_nr > other_tie.seq_nr {
+                if tie > *other_tie {
                     entry.remove_entry();
                     self.transmit_ties.insert(tie);
                 }
-                todo!();
             } else {
                 self.transmit_ties.insert(tie);
             }
@@ -569,18 +739,16 @@ impl TieStateMachine {
         self.tie_been_acked(tie);
     }
     /// if not is_request_filtered(TIE) then remove_from_all_queues(TIE) and add to TIES_REQ.
-    fn request_tie(&mut self, tie: TIEHeader) {
-        if !self.is_request_filtered(&tie) {
+    fn request_tie(&mut self, link_info: &LinkInfo, tie: TIEHeader) {
+        if !self.is_request_filtered(link_info, &tie) {
             self.remove_from_all_queues(tie);
             self.requested_ties.insert(tie);
         }
     }
-    /// Seemingly not used in the spec?
     /// remove TIE from TIES_TX and then add to TIES_RTX using TIE retransmission interval.
-    fn _move_to_rtx_list(&mut self, tie: TIEHeader) {
+    fn move_to_rtx_list(&mut self, tie: TIEHeader) {
         self.transmit_ties.remove(&tie.tie_id);
-        self.retransmit_ties.remove(&tie.tie_id);
-        todo!(); // TODO: retransmission interval
+        self.retransmit_ties.insert(tie);
     }
 
     /// Seemingly not used in the spec?
@@ -595,8 +763,13 @@ impl TieStateMachine {
     /// self-originiated, so a check here is not needed.
     /// for self-originated TIE originate an empty or re-generate with version number higher then
     /// the one in TIE
-    fn bump_own_tie(&mut self, tie: &TIEHeader) {
-        todo!()
+    fn bump_own_tie(
+        &mut self,
+        link_info: &LinkInfo,
+        ls_db: &mut LinkStateDatabase,
+        tie: &TIEHeader,
+    ) {
+        ls_db.bump_own_tie(tie, link_info.local_level);
     }
 }
 
@@ -608,7 +781,8 @@ pub struct LinkInfo {
 }
 
 impl LinkInfo {
-    fn direction(&self) -> LinkDirection {
+    /// The direction of the link, as seen from this node.
+    pub fn direction(&self) -> LinkDirection {
         if self.local_level < self.neighbor.level {
             LinkDirection::North
         } else if self.local_level > self.neighbor.level {
@@ -626,8 +800,29 @@ pub enum LinkDirection {
     EastWest,
 }
 
-fn tie_has_content(tie: &TIEPacket) -> bool {
-    todo!()
+/// Returns the level of the originator of the given TIE. This is only known for Node TIEs whose
+/// content is in the LSDB, so callers must handle None: the TIE may be missing from the LSDB, or
+/// only its header may be known (for instance, because it was purged).
+fn originator_level(ls_db: &LinkStateDatabase, tie: &TIEHeader) -> Option<u8> {
+    // Implementation adapted from `rift-python`. We cannot determine the level of the originator
+    // just by looking at the TIE header; we have to look in the LSDB to determine it. Also, this
+    // question can only be asked about Node TIEs (other TIEs don't store the level of the
+    // originator in the TIEPacket).
+    match ls_db.find(tie) {
+        Some(DBTie::Content(packet)) => match packet.element {
+            encoding::TIEElement::Node(node) => Some(node.level as u8),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns true if the TIE in the LSDB has content (that is, it is more than just a header).
+fn tie_has_content(tie: &DBTie) -> bool {
+    match tie {
+        DBTie::Content(_) => true,
+        DBTie::HeaderOnly(_) => false,
+    }
 }
 
 struct TIECollection {
@@ -656,27 +851,1058 @@ impl TIECollection {
     fn iter(&self) -> impl Iterator<Item = &TIEHeader> {
         self.ties.values()
     }
+
+    /// Remove all TIEs from the collection, returning them in sorted order.
+    fn take_all(&mut self) -> Vec<TIEHeader> {
+        std::mem::take(&mut self.ties).into_values().collect()
+    }
+}
+
+/// Configuration for how TIEs on TIES_RTX are retransmitted.
+#[derive(Debug, Clone, Copy)]
+pub struct RetransmitConfig {
+    /// How long to wait for a TIE to be acknowledged before sending it again.
+    pub interval: Duration,
+    /// The maximum number of times a TIE is sent before giving up on it. TIEs which are given up on
+    /// will still eventually be sent again due to TIDE exchange.
+    pub max_attempts: usize,
+}
+
+impl Default for RetransmitConfig {
+    fn default() -> Self {
+        RetransmitConfig {
+            interval: Duration::from_secs(1),
+            max_attempts: 5,
+        }
+    }
+}
+
+/// Configuration for flooding reduction (see [flood_repeaters]).
+#[derive(Debug, Clone, Copy)]
+pub struct FloodReductionConfig {
+    /// If false, every northbound neighbor is a flood repeater.
+    pub enabled: bool,
+    /// The number of flood repeaters which should cover each grandparent.
+    pub redundancy: usize,
+    /// Parents whose number of grandparents differ by at most this much are considered equally
+    /// good flood repeaters.
+    pub similarity: usize,
+}
+
+impl Default for FloodReductionConfig {
+    fn default() -> Self {
+        FloodReductionConfig {
+            enabled: true,
+            redundancy: 2,
+            similarity: 2,
+        }
+    }
 }
-struct LinkStateDatabase {
-    ties: BTreeMap<TIEID, TIEPacket>,
+
+/// 4.2.3.9. Flooding Reduction
+/// Elect the flood repeaters among `parents`, the northbound neighbors of the node `root`. Only the
+/// flood repeaters reflood the North TIEs originated by `root` further north, which avoids every
+/// grandparent receiving a copy from every parent. The grandparents of each parent are found from
+/// the parent's Node TIEs. If flooding reduction is disabled, every parent is a flood repeater.
+/// Otherwise, the election works as follows:
+/// 1. Sort the parents by the number of grandparents they cover, in decreasing order.
+/// 2. Split the sorted parents into similarity groups. A parent belongs to the current group if it
+///    covers at most `similarity` fewer grandparents than the first parent of the group.
+/// 3. Shuffle each group using a random number generator seeded with the system ID of `root`, so
+///    that different nodes spread the load across different parents, but the election of a given
+///    node is stable.
+/// 4. Going through the groups in order, elect every parent which covers some grandparent that is
+///    not yet covered by `redundancy` flood repeaters.
+///
+/// A parent whose grandparents are not known yet (because its Node TIEs have not been received) is
+/// always elected, so that a fresh adjacency does not suppress flooding.
+pub fn flood_repeaters(
+    ls_db: &LinkStateDatabase,
+    root: SystemID,
+    parents: &BTreeSet<SystemID>,
+    config: FloodReductionConfig,
+) -> BTreeSet<SystemID> {
+    if !config.enabled {
+        return parents.clone();
+    }
+
+    let mut grandparents: BTreeMap<SystemID, BTreeSet<SystemID>> = parents
+        .iter()
+        .map(|&parent| (parent, BTreeSet::new()))
+        .collect();
+    for tie in ls_db.iter() {
+        let DBTie::Content(tie) = tie else {
+            continue;
+        };
+        let encoding::TIEElement::Node(node) = &tie.element else {
+            continue;
+        };
+        let Some(covered) = grandparents.get_mut(&tie.header.tie_id.originator) else {
+            continue;
+        };
+        let northbound = node
+            .neighbors
+            .iter()
+            .filter(|(_, neighbor)| neighbor.level > node.level)
+            .filter_map(|(&system_id, _)| SystemID::try_from(system_id).ok());
+        covered.extend(northbound);
+    }
+
+    let mut sorted = grandparents.into_iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|(_, covered)| Reverse(covered.len()));
+    let mut groups: Vec<Vec<(SystemID, BTreeSet<SystemID>)>> = vec![];
+    for parent in sorted {
+        match groups.last_mut() {
+            Some(group) if group[0].1.len() - parent.1.len() <= config.similarity => {
+                group.push(parent)
+            }
+            _ => groups.push(vec![parent]),
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(root.get() as u64 ^ 0x5555_5555_5555_5555);
+    let mut coverage: BTreeMap<SystemID, usize> = BTreeMap::new();
+    let mut repeaters = BTreeSet::new();
+    for group in &mut groups {
+        group.shuffle(&mut rng);
+        for (parent, covered) in group.iter() {
+            let is_needed = covered.iter().any(|grandparent| {
+                coverage.get(grandparent).copied().unwrap_or(0) < config.redundancy
+            });
+            if is_needed || covered.is_empty() {
+                repeaters.insert(*parent);
+                for &grandparent in covered {
+                    *coverage.entry(grandparent).or_default() += 1;
+                }
+            }
+        }
+    }
+    repeaters
+}
+
+/// The TIES_RTX collection. This is like a [TIECollection], except that each TIE also tracks
+/// when it should next be retransmitted and how many times it has been sent.
+struct RetransmitQueue {
+    ties: BTreeMap<TIEID, RetransmitEntry>,
+    config: RetransmitConfig,
+}
+
+struct RetransmitEntry {
+    header: TIEHeader,
+    deadline: Instant,
+    attempts: usize,
+}
+
+impl RetransmitQueue {
+    fn new(config: RetransmitConfig) -> RetransmitQueue {
+        RetransmitQueue {
+            ties: BTreeMap::new(),
+            config,
+        }
+    }
+
+    /// Insert a TIE which was just sent for the first time.
+    fn insert(&mut self, tie: TIEHeader) {
+        let entry = RetransmitEntry {
+            header: tie,
+            deadline: Instant::now() + self.config.interval,
+            attempts: 1,
+        };
+        self.ties.insert(tie.tie_id, entry);
+    }
+
+    fn remove(&mut self, tie_id: &TIEID) -> Option<TIEHeader> {
+        self.ties.remove(tie_id).map(|entry| entry.header)
+    }
+
+    /// Returns the TIEs whose retransmission deadline has passed, and restarts their deadlines.
+    /// TIEs which have already been sent `max_attempts` times are dropped instead.
+    fn take_expired(&mut self) -> Vec<TIEHeader> {
+        let now = Instant::now();
+        let mut expired = vec![];
+        let config = self.config;
+        self.ties.retain(|tie_id, entry| {
+            if now < entry.deadline {
+                true
+            } else if entry.attempts >= config.max_attempts {
+                tracing::debug!(tie_id =? tie_id, attempts = entry.attempts, "giving up on retransmitting TIE");
+                false
+            } else {
+                entry.attempts += 1;
+                entry.deadline = now + config.interval;
+                expired.push(entry.header);
+                true
+            }
+        });
+        expired
+    }
+}
+
+/// A TIE as stored in the LSDB (the "DBTIE" of the spec). Usually the LSDB holds the full TIE, but
+/// TIDE processing may override a TIE with just a newer header (see step 6.i of
+/// [TieStateMachine::process_tide]). Such header-only entries have no content until the full TIE
+/// is received, and are kept distinct so that the flooding procedures can tell the two apart.
+#[derive(Clone, Debug)]
+pub enum DBTie {
+    /// A TIE whose content is known.
+    Content(TIEPacket),
+    /// A TIE for which only the header is known.
+    HeaderOnly(TIEHeader),
+}
+
+impl DBTie {
+    /// The header of the TIE, regardless of whether the TIE has content or not.
+    pub fn header(&self) -> TIEHeader {
+        match self {
+            DBTie::Content(tie) => tie.header,
+            DBTie::HeaderOnly(header) => *header,
+        }
+    }
+}
+
+/// An element with no content for the given type of TIE. This is used when purging TIEs. Node TIEs
+/// always carry the level of their originator, so an empty Node TIE still advertises `level`.
+fn empty_element(tie_type: TIESubtype, level: u8) -> encoding::TIEElement {
+    let prefixes = encoding::PrefixTIEElement::new(BTreeMap::new());
+    match tie_type {
+        TIESubtype::Node => encoding::TIEElement::Node(wrapper::node_element(
+            level as common::LevelType,
+            BTreeMap::new(),
+        )),
+        TIESubtype::Prefix | TIESubtype::PGPrefix => encoding::TIEElement::Prefixes(prefixes),
+        TIESubtype::PositiveDisaggregationPrefix => {
+            encoding::TIEElement::PositiveDisaggregationPrefixes(prefixes)
+        }
+        TIESubtype::NegativeDisaggregationPrefix => {
+            encoding::TIEElement::NegativeDisaggregationPrefixes(prefixes)
+        }
+        TIESubtype::ExternalPrefix => encoding::TIEElement::ExternalPrefixes(prefixes),
+        TIESubtype::PositiveExternalDisaggregation => {
+            encoding::TIEElement::PositiveExternalDisaggregationPrefixes(prefixes)
+        }
+        TIESubtype::KeyValue => {
+            encoding::TIEElement::Keyvalues(encoding::KeyValueTIEElement::new(BTreeMap::new()))
+        }
+    }
+}
+
+/// The link state database. This stores every TIE known to the node, keyed by TIEID. Since the
+/// TIEID space is totally ordered, the LSDB also supports range queries, which are used for TIDE
+/// generation and processing. The LSDB also tracks the remaining lifetime of each TIE, which
+/// counts down in real time (see [LinkStateDatabase::age]).
+#[derive(Default)]
+pub struct LinkStateDatabase {
+    ties: BTreeMap<TIEID, LSDBEntry>,
+    /// The headers of TIEs which were inserted since the last call to [LinkStateDatabase::take_new_ties],
+    /// along with the neighbor each TIE was received from, if any. These TIEs still need to be
+    /// flooded out of the node's other adjacencies.
+    new_ties: Vec<(TIEHeader, Option<SystemID>)>,
+    /// True if the contents of the LSDB changed since the last call to
+    /// [LinkStateDatabase::take_changed]. This is used to decide when routes need recomputing.
+    changed: bool,
+}
+
+/// A TIE in the LSDB, along with the bookkeeping required for aging it.
+struct LSDBEntry {
+    tie: DBTie,
+    /// If the TIE is not purged, then this is when the TIE's remaining lifetime reaches zero. If
+    /// the TIE is purged, then this is when the TIE should be removed from the LSDB entirely.
+    deadline: Instant,
+    /// True if the TIE's lifetime has run out. Purged TIEs have their content discarded, but their
+    /// headers are kept around for `PURGE_LIFETIME` to prevent the TIE from being reflooded.
+    purged: bool,
+    /// True if this is a self-originated TIE which should be refreshed before it expires.
+    refresh: bool,
+}
+
+impl LSDBEntry {
+    fn new(tie: DBTie, remaining_lifetime: LifetimeInSecs, refresh: bool) -> LSDBEntry {
+        LSDBEntry {
+            tie,
+            deadline: Instant::now() + Duration::from_secs(remaining_lifetime as u64),
+            purged: false,
+            refresh,
+        }
+    }
+
+    fn remaining_lifetime(&self) -> LifetimeInSecs {
+        if self.purged {
+            0
+        } else {
+            let remaining = self.deadline.saturating_duration_since(Instant::now());
+            remaining.as_secs() as LifetimeInSecs
+        }
+    }
 }
 
 impl LinkStateDatabase {
-    fn new() -> LinkStateDatabase {
+    pub fn new() -> LinkStateDatabase {
         LinkStateDatabase {
             ties: BTreeMap::new(),
+            new_ties: vec![],
+            changed: false,
+        }
+    }
+
+    /// Find the TIE with the same TIEID as the given header. Note that the returned TIE may be
+    /// older, newer, or the same version as the header--the caller is expected to compare them.
+    pub fn find(&self, header: &TIEHeader) -> Option<DBTie> {
+        self.ties.get(&header.tie_id).map(|entry| entry.tie.clone())
+    }
+
+    /// Returns the header of the TIE with the given TIEID along with its current remaining lifetime.
+    pub fn header_with_lifetime(&self, tie_id: &TIEID) -> Option<TIEHeaderWithLifetime> {
+        self.ties.get(tie_id).map(|entry| TIEHeaderWithLifetime {
+            header: entry.tie.header(),
+            remaining_lifetime: entry.remaining_lifetime(),
+        })
+    }
+
+    /// Returns true if the TIE with the given TIEID is in the LSDB and has content.
+    pub fn has_content(&self, tie_id: &TIEID) -> bool {
+        self.ties
+            .get(tie_id)
+            .is_some_and(|entry| tie_has_content(&entry.tie))
+    }
+
+    /// Returns the remaining lifetime of the TIE with the given TIEID. This is zero if the TIE is
+    /// not in the LSDB or has been purged.
+    pub fn remaining_lifetime(&self, tie_id: &TIEID) -> LifetimeInSecs {
+        self.ties
+            .get(tie_id)
+            .map_or(0, |entry| entry.remaining_lifetime())
+    }
+
+    /// Override the TIE in the LSDB with the given header. The content of the old TIE is discarded,
+    /// leaving a header-only entry.
+    pub fn replace(&mut self, header: &TIEHeaderWithLifetime) {
+        let entry = LSDBEntry::new(
+            DBTie::HeaderOnly(header.header),
+            header.remaining_lifetime,
+            false,
+        );
+        self.ties.insert(header.header.tie_id, entry);
+        self.changed = true;
+    }
+
+    /// Insert the TIE into the LSDB, returning the previously stored TIE with the same TIEID, if any.
+    /// This does not check the version of the TIE, so the caller is expected to only insert TIEs
+    /// which are newer than (or the same as) the one already in the LSDB.
+    pub fn insert(&mut self, tie: &TIEPacket, remaining_lifetime: LifetimeInSecs) -> Option<DBTie> {
+        self.insert_entry(tie, remaining_lifetime, false, None)
+    }
+
+    /// Same as [LinkStateDatabase::insert], but for a TIE which was received from `neighbor`. The
+    /// TIE is then not flooded back to that neighbor (see [LinkStateDatabase::take_new_ties]).
+    pub fn insert_received(
+        &mut self,
+        tie: &TIEPacket,
+        remaining_lifetime: LifetimeInSecs,
+        neighbor: SystemID,
+    ) -> Option<DBTie> {
+        self.insert_entry(tie, remaining_lifetime, false, Some(neighbor))
+    }
+
+    fn insert_entry(
+        &mut self,
+        tie: &TIEPacket,
+        remaining_lifetime: LifetimeInSecs,
+        refresh: bool,
+        received_from: Option<SystemID>,
+    ) -> Option<DBTie> {
+        self.new_ties.push((tie.header, received_from));
+        self.changed = true;
+        let entry = LSDBEntry::new(DBTie::Content(tie.clone()), remaining_lifetime, refresh);
+        self.ties
+            .insert(tie.header.tie_id, entry)
+            .map(|entry| entry.tie)
+    }
+
+    /// Returns all TIEs whose TIEIDs fall within the given range, in sorted order.
+    pub fn range(&self, range: impl RangeBounds<TIEID>) -> impl Iterator<Item = &DBTie> {
+        self.ties.range(range).map(|(_, entry)| &entry.tie)
+    }
+
+    /// Returns all TIEs in the LSDB, in sorted order.
+    pub fn iter(&self) -> impl Iterator<Item = &DBTie> {
+        self.ties.values().map(|entry| &entry.tie)
+    }
+
+    /// Originate a TIE from this node with the given element. If the LSDB already contains the same
+    /// content under this TIEID, nothing happens. Otherwise, the TIE is inserted with a sequence
+    /// number one higher than the existing TIE (or 1 if there is no existing TIE), which causes it
+    /// to be flooded. Returns true if a new version of the TIE was originated.
+    pub fn originate(&mut self, tie_id: TIEID, element: encoding::TIEElement) -> bool {
+        let seq_nr = match self.ties.get(&tie_id) {
+            Some(entry) => match &entry.tie {
+                DBTie::Content(tie) if tie.element == element => return false,
+                tie => tie.header().seq_nr + 1,
+            },
+            None => 1,
+        };
+        self.originate_with_seq_nr(tie_id, seq_nr, element, common::DEFAULT_LIFETIME, true);
+        true
+    }
+
+    /// Re-originate a self-originated TIE with a sequence number higher than the one in the given
+    /// header. This happens when a neighbor has a newer version of one of our TIEs (for example,
+    /// because this node restarted). If the LSDB has content for the TIE, then that content is
+    /// re-originated. Otherwise, the node no longer originates the TIE, so an empty TIE with a
+    /// lifetime of `PURGE_LIFETIME` is originated instead, which purges it from the network. The
+    /// level of this node is given by `level`, which is advertised if the purged TIE is a Node TIE.
+    pub fn bump_own_tie(&mut self, header: &TIEHeader, level: u8) {
+        let entry = self.ties.get(&header.tie_id);
+        let seq_nr = match entry {
+            Some(entry) => entry.tie.header().seq_nr.max(header.seq_nr) + 1,
+            None => header.seq_nr + 1,
+        };
+        let (element, lifetime, refresh) = match entry {
+            Some(LSDBEntry {
+                tie: DBTie::Content(tie),
+                refresh: true,
+                ..
+            }) => (tie.element.clone(), common::DEFAULT_LIFETIME, true),
+            _ => (
+                empty_element(header.tie_id.tie_type, level),
+                common::PURGE_LIFETIME,
+                false,
+            ),
+        };
+        tracing::info!(tie_id =? header.tie_id, seq_nr, "bumped own TIE");
+        self.originate_with_seq_nr(header.tie_id, seq_nr, element, lifetime, refresh);
+    }
+
+    fn originate_with_seq_nr(
+        &mut self,
+        tie_id: TIEID,
+        seq_nr: SequenceNumber,
+        element: encoding::TIEElement,
+        lifetime: common::LifeTimeInSecType,
+        refresh: bool,
+    ) {
+        let tie = TIEPacket {
+            header: TIEHeader {
+                tie_id,
+                seq_nr,
+                origination_time: Some(IEEE8021ASTimeStamp::now()),
+                origination_lifetime: Some(lifetime as LifetimeInSecs),
+            },
+            element,
+        };
+        self.insert_entry(&tie, lifetime as LifetimeInSecs, refresh, None);
+    }
+
+    /// Age the TIEs in the LSDB. This should be called periodically. Specifically:
+    /// - Self-originated TIEs which have less than half of `DEFAULT_LIFETIME` remaining are
+    ///   refreshed by re-originating them with a higher sequence number.
+    /// - TIEs whose remaining lifetime has reached zero are purged. Their content is discarded and
+    ///   only the header is kept for another `PURGE_LIFETIME`.
+    /// - Purged TIEs which have been kept for `PURGE_LIFETIME` are removed.
+    pub fn age(&mut self) {
+        let now = Instant::now();
+        let refresh_lifetime = (common::DEFAULT_LIFETIME / 2) as LifetimeInSecs;
+
+        let mut refresh = vec![];
+        let mut changed = false;
+        self.ties.retain(|tie_id, entry| {
+            if entry.purged {
+                return now < entry.deadline;
+            }
+
+            if entry.refresh && entry.remaining_lifetime() < refresh_lifetime {
+                if let DBTie::Content(tie) = &entry.tie {
+                    refresh.push(tie.clone());
+                }
+            } else if now >= entry.deadline {
+                tracing::info!(tie_id =? tie_id, "TIE expired, purging");
+                entry.tie = DBTie::HeaderOnly(entry.tie.header());
+                entry.deadline = now + Duration::from_secs(common::PURGE_LIFETIME as u64);
+                entry.purged = true;
+                changed = true;
+            }
+            true
+        });
+        self.changed |= changed;
+
+        for tie in refresh {
+            tracing::debug!(tie_id =? tie.header.tie_id, "refreshing own TIE");
+            self.originate_with_seq_nr(
+                tie.header.tie_id,
+                tie.header.seq_nr + 1,
+                tie.element,
+                common::DEFAULT_LIFETIME,
+                true,
+            );
+        }
+    }
+
+    /// Returns the headers of all TIEs inserted since this function was last called, along with
+    /// the neighbor each TIE was received from. This is None for TIEs originated by this node.
+    pub fn take_new_ties(&mut self) -> Vec<(TIEHeader, Option<SystemID>)> {
+        std::mem::take(&mut self.new_ties)
+    }
+
+    /// Returns true if any TIE was inserted, replaced, or purged since this function was last
+    /// called.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cmp::Ordering,
+        collections::{BTreeMap, BTreeSet},
+        net::Ipv4Addr,
+        time::Duration,
+    };
+
+    use crate::{
+        lie_exchange::Neighbor,
+        models::{common, encoding},
+        wrapper::{
+            self, LifetimeInSecs, SystemID, TIDEPacket, TIEHeader, TIEHeaderWithLifetime,
+            TIEPacket, TIESubtype, TIREPacket, TieDirection, TieNumber, TIEID, TOP_OF_FABRIC_LEVEL,
+        },
+    };
+
+    const LIFETIME: LifetimeInSecs = common::DEFAULT_LIFETIME as LifetimeInSecs;
+
+    use super::{
+        flood_repeaters, DBTie, FloodReductionConfig, LinkInfo, LinkStateDatabase,
+        RetransmitConfig, RetransmitQueue, TieStateMachine,
+    };
+
+    fn header(originator: i64, tie_nr: i32, seq_nr: u32) -> TIEHeader {
+        TIEHeader {
+            tie_id: TIEID {
+                direction: TieDirection::North,
+                originator: SystemID::try_from(originator).unwrap(),
+                tie_type: TIESubtype::Prefix,
+                tie_nr: TieNumber::try_from(tie_nr).unwrap(),
+            },
+            seq_nr,
+            origination_time: None,
+            origination_lifetime: None,
+        }
+    }
+
+    /// A header like [header], but with the given direction and TIE type.
+    fn scoped_header(direction: TieDirection, originator: i64, tie_type: TIESubtype) -> TIEHeader {
+        let mut header = header(originator, 1, 1);
+        header.tie_id.direction = direction;
+        header.tie_id.tie_type = tie_type;
+        header
+    }
+
+    fn tie(header: TIEHeader) -> TIEPacket {
+        TIEPacket {
+            header,
+            element: encoding::TIEElement::Prefixes(encoding::PrefixTIEElement::new(
+                BTreeMap::new(),
+            )),
+        }
+    }
+
+    /// A South Node TIE originated by `originator` at the given level.
+    fn node_tie(
+        originator: i64,
+        level: common::LevelType,
+        neighbors: BTreeMap<common::SystemIDType, encoding::NodeNeighborsTIEElement>,
+    ) -> TIEPacket {
+        let mut node_tie = tie(scoped_header(
+            TieDirection::South,
+            originator,
+            TIESubtype::Node,
+        ));
+        node_tie.element = encoding::TIEElement::Node(wrapper::node_element(level, neighbors));
+        node_tie
+    }
+
+    /// The link from node 1 to node 2, at the given levels.
+    fn link_info(local_level: u8, neighbor_level: u8) -> LinkInfo {
+        LinkInfo {
+            local_level,
+            local_system_id: SystemID::try_from(1).unwrap(),
+            neighbor: Neighbor {
+                level: neighbor_level,
+                address: Ipv4Addr::LOCALHOST.into(),
+                system_id: SystemID::try_from(2).unwrap(),
+                flood_port: 0,
+                name: None,
+                local_link_id: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_lsdb_find_insert_replace() {
+        let mut ls_db = LinkStateDatabase::new();
+        assert!(ls_db.find(&header(1, 1, 1)).is_none());
+
+        ls_db.insert(&tie(header(1, 1, 1)), LIFETIME);
+        // Lookup is done by TIEID, so an older or newer header still finds the TIE.
+        let db_tie = ls_db.find(&header(1, 1, 5)).unwrap();
+        assert!(matches!(db_tie, DBTie::Content(_)));
+        assert!(db_tie.header() < header(1, 1, 5));
+        assert!(db_tie.header() == header(1, 1, 1));
+
+        ls_db.replace(&TIEHeaderWithLifetime::new(header(1, 1, 5)));
+        let db_tie = ls_db.find(&header(1, 1, 1)).unwrap();
+        assert!(matches!(db_tie, DBTie::HeaderOnly(_)));
+        assert!(db_tie.header() == header(1, 1, 5));
+
+        ls_db.insert(&tie(header(1, 1, 5)), LIFETIME);
+        assert!(matches!(
+            ls_db.find(&header(1, 1, 5)).unwrap(),
+            DBTie::Content(_)
+        ));
+    }
+
+    #[test]
+    fn test_lsdb_range() {
+        let mut ls_db = LinkStateDatabase::new();
+        for (originator, tie_nr) in [(3, 1), (1, 2), (2, 1), (1, 1)] {
+            ls_db.insert(&tie(header(originator, tie_nr, 1)), LIFETIME);
+        }
+
+        let start = header(1, 2, 1).tie_id;
+        let end = header(3, 1, 1).tie_id;
+        let tie_ids = ls_db
+            .range(start..end)
+            .map(|tie| tie.header().tie_id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tie_ids,
+            vec![header(1, 2, 1).tie_id, header(2, 1, 1).tie_id]
+        );
+        assert_eq!(ls_db.iter().count(), 4);
+    }
+
+    #[test]
+    fn test_lsdb_originate() {
+        let mut ls_db = LinkStateDatabase::new();
+        let tie_id = header(1, 1, 1).tie_id;
+        let element = tie(header(1, 1, 1)).element;
+
+        assert!(ls_db.originate(tie_id, element.clone()));
+        assert_eq!(ls_db.find(&header(1, 1, 1)).unwrap().header().seq_nr, 1);
+
+        // Originating the same content again should not bump the sequence number.
+        assert!(!ls_db.originate(tie_id, element));
+        assert_eq!(ls_db.find(&header(1, 1, 1)).unwrap().header().seq_nr, 1);
+
+        let element = encoding::TIEElement::PositiveDisaggregationPrefixes(
+            encoding::PrefixTIEElement::new(BTreeMap::new()),
+        );
+        assert!(ls_db.originate(tie_id, element));
+        assert_eq!(ls_db.find(&header(1, 1, 1)).unwrap().header().seq_nr, 2);
+        assert_eq!(ls_db.take_new_ties().len(), 2);
+    }
+
+    #[test]
+    fn test_lsdb_bump_own_tie() {
+        let mut ls_db = LinkStateDatabase::new();
+        let tie_id = header(1, 1, 1).tie_id;
+        let element = tie(header(1, 1, 1)).element;
+        ls_db.originate(tie_id, element.clone());
+
+        // A neighbor has a newer version, so our content is re-originated above it.
+        ls_db.bump_own_tie(&header(1, 1, 7), 1);
+        match ls_db.find(&header(1, 1, 1)).unwrap() {
+            DBTie::Content(tie) => {
+                assert_eq!(tie.header.seq_nr, 8);
+                assert_eq!(tie.element, element);
+            }
+            DBTie::HeaderOnly(_) => panic!("expected TIE with content"),
         }
+
+        // We don't have this TIE at all, so it is purged with a short lifetime.
+        ls_db.bump_own_tie(&header(1, 2, 3), 1);
+        let purged = ls_db.find(&header(1, 2, 3)).unwrap().header();
+        assert_eq!(purged.seq_nr, 4);
+        assert_eq!(
+            purged.origination_lifetime,
+            Some(common::PURGE_LIFETIME as u32)
+        );
+
+        // Purged Node TIEs still advertise the level of this node.
+        let mut node_header = header(1, 3, 1);
+        node_header.tie_id.tie_type = TIESubtype::Node;
+        ls_db.bump_own_tie(&node_header, 2);
+        match ls_db.find(&node_header).unwrap() {
+            DBTie::Content(TIEPacket {
+                element: encoding::TIEElement::Node(node),
+                ..
+            }) => assert_eq!(node.level, 2),
+            tie => panic!("expected Node TIE, got {tie:?}"),
+        }
+    }
+
+    #[test]
+    fn test_lsdb_age() {
+        let mut ls_db = LinkStateDatabase::new();
+        ls_db.insert(&tie(header(1, 1, 1)), LIFETIME);
+        ls_db.insert(&tie(header(2, 1, 1)), 0);
+        ls_db.age();
+
+        // The TIE with no lifetime left is purged, but its header is kept around.
+        let purged = ls_db.find(&header(2, 1, 1)).unwrap();
+        assert!(matches!(purged, DBTie::HeaderOnly(_)));
+        assert_eq!(ls_db.remaining_lifetime(&header(2, 1, 1).tie_id), 0);
+
+        assert!(matches!(
+            ls_db.find(&header(1, 1, 1)).unwrap(),
+            DBTie::Content(_)
+        ));
+        assert!(ls_db.remaining_lifetime(&header(1, 1, 1).tie_id) > LIFETIME - 5);
+    }
+
+    #[test]
+    fn test_compare_lifetime() {
+        let with_lifetime = |seq_nr, remaining_lifetime| TIEHeaderWithLifetime {
+            header: header(1, 1, seq_nr),
+            remaining_lifetime,
+        };
+        let diff2ignore = common::LIFETIME_DIFF2IGNORE as LifetimeInSecs;
+
+        assert_eq!(
+            with_lifetime(1, LIFETIME).compare(&with_lifetime(1, LIFETIME - diff2ignore + 1)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            with_lifetime(1, LIFETIME).compare(&with_lifetime(1, LIFETIME - diff2ignore)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            with_lifetime(1, 0).compare(&with_lifetime(1, LIFETIME)),
+            Ordering::Less
+        );
+        assert_eq!(
+            with_lifetime(2, 0).compare(&with_lifetime(1, LIFETIME)),
+            Ordering::Greater
+        );
     }
 
-    fn find(&self, header: &TIEHeader) -> Option<TIEPacket> {
-        todo!()
+    #[test]
+    fn test_retransmit_queue() {
+        let config = RetransmitConfig {
+            interval: Duration::ZERO,
+            max_attempts: 3,
+        };
+        let mut queue = RetransmitQueue::new(config);
+        queue.insert(header(1, 1, 1));
+
+        // The first send counts as an attempt, so the TIE is retransmitted twice before being dropped.
+        assert_eq!(queue.take_expired(), vec![header(1, 1, 1)]);
+        assert_eq!(queue.take_expired(), vec![header(1, 1, 1)]);
+        assert_eq!(queue.take_expired(), vec![]);
+        assert!(queue.remove(&header(1, 1, 1).tie_id).is_none());
+
+        // Acked TIEs are removed and never retransmitted.
+        queue.insert(header(2, 1, 1));
+        assert!(queue.remove(&header(2, 1, 1).tie_id).is_some());
+        assert_eq!(queue.take_expired(), vec![]);
     }
 
-    fn replace(&self, db_header: &TIEPacket, header: &TIEHeader) {
-        todo!()
+    #[test]
+    fn test_try_to_transmit_tie() {
+        let mut tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let ls_db = LinkStateDatabase::new();
+        let north = link_info(1, 2);
+        let old = scoped_header(TieDirection::South, 2, TIESubtype::Prefix);
+        let mut new = old;
+        new.seq_nr += 1;
+
+        // Sending a newer version of a TIE replaces both the pending request and the pending
+        // retransmission of the old version.
+        tie_fsm.requested_ties.insert(old);
+        tie_fsm.retransmit_ties.insert(old);
+        tie_fsm.try_to_transmit_tie(&north, &ls_db, new);
+        assert_eq!(tie_fsm.requested_ties.iter().count(), 0);
+        assert!(tie_fsm.retransmit_ties.remove(&old.tie_id).is_none());
+        assert_eq!(tie_fsm.transmit_ties.iter().collect::<Vec<_>>(), vec![&new]);
     }
 
-    fn insert(&mut self, tie: &TIEPacket) {
-        todo!()
+    #[test]
+    fn test_tide_covering_purged_node_tie() {
+        let mut tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let mut ls_db = LinkStateDatabase::new();
+        let node_tie = node_tie(3, 1, BTreeMap::new());
+        ls_db.insert(&node_tie, 0);
+        ls_db.age();
+        assert!(matches!(
+            ls_db.find(&node_tie.header).unwrap(),
+            DBTie::HeaderOnly(_)
+        ));
+
+        // The purged TIE falls within the range of the TIDE, so it is considered for sending, but
+        // without its content the level of the originator is unknown.
+        let tide = TIDEPacket {
+            start_range: None,
+            end_range: None,
+            headers: vec![],
+        };
+        for link_info in [link_info(1, 0), link_info(1, 2)] {
+            tie_fsm
+                .process_tide(&link_info, &mut ls_db, false, &tide)
+                .unwrap();
+            assert_eq!(tie_fsm.transmit_ties.iter().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_generate_tire() {
+        let mut tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let ls_db = LinkStateDatabase::new();
+        let north = link_info(1, 2);
+        let requested = scoped_header(TieDirection::South, 3, TIESubtype::Prefix);
+        tie_fsm.ack_tie(header(4, 1, 1));
+        tie_fsm.request_tie(&north, requested);
+        let headers = |tires: Vec<TIREPacket>| {
+            tires
+                .into_iter()
+                .flat_map(|tire| tire.headers)
+                .map(|header| header.header)
+                .collect::<Vec<_>>()
+        };
+
+        // Acknowledgements are only sent once.
+        assert_eq!(
+            headers(tie_fsm.generate_tire(&ls_db, 10, false)),
+            vec![header(4, 1, 1)]
+        );
+        assert!(tie_fsm.generate_tire(&ls_db, 10, false).is_empty());
+
+        // Requests are sent until the TIE arrives.
+        for _ in 0..2 {
+            assert_eq!(
+                headers(tie_fsm.generate_tire(&ls_db, 10, true)),
+                vec![requested]
+            );
+        }
+    }
+
+    #[test]
+    fn test_tide_entry_filtered() {
+        let tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let mut ls_db = LinkStateDatabase::new();
+        ls_db.insert(&node_tie(3, 1, BTreeMap::new()), LIFETIME);
+        let filtered = |link_info: &LinkInfo, direction, originator, tie_type| {
+            let header = scoped_header(direction, originator, tie_type);
+            tie_fsm.is_tide_entry_filtered(link_info, &ls_db, &header)
+        };
+
+        // Southbound TIDEs hold non-self-originated North TIEs, self-originated South TIEs and
+        // South Node TIEs of nodes on the same level.
+        let south = link_info(1, 0);
+        assert!(!filtered(
+            &south,
+            TieDirection::North,
+            2,
+            TIESubtype::Prefix
+        ));
+        assert!(filtered(&south, TieDirection::North, 1, TIESubtype::Prefix));
+        assert!(!filtered(
+            &south,
+            TieDirection::South,
+            1,
+            TIESubtype::Prefix
+        ));
+        assert!(filtered(&south, TieDirection::South, 2, TIESubtype::Prefix));
+        assert!(!filtered(&south, TieDirection::South, 3, TIESubtype::Node));
+        assert!(filtered(
+            &link_info(2, 1),
+            TieDirection::South,
+            3,
+            TIESubtype::Node
+        ));
+
+        // Northbound TIDEs hold all North TIEs, all South Node TIEs and the peer's South TIEs.
+        let north = link_info(1, 2);
+        assert!(!filtered(
+            &north,
+            TieDirection::North,
+            3,
+            TIESubtype::Prefix
+        ));
+        assert!(!filtered(&north, TieDirection::South, 3, TIESubtype::Node));
+        assert!(!filtered(
+            &north,
+            TieDirection::South,
+            2,
+            TIESubtype::Prefix
+        ));
+        assert!(filtered(&north, TieDirection::South, 3, TIESubtype::Prefix));
+
+        // East-West TIDEs hold all North TIEs on the ToF, and only self-originated TIEs otherwise.
+        let tof = link_info(TOP_OF_FABRIC_LEVEL, TOP_OF_FABRIC_LEVEL);
+        assert!(!filtered(&tof, TieDirection::North, 3, TIESubtype::Prefix));
+        assert!(filtered(&tof, TieDirection::South, 1, TIESubtype::Prefix));
+        let east_west = link_info(1, 1);
+        assert!(!filtered(
+            &east_west,
+            TieDirection::South,
+            1,
+            TIESubtype::Prefix
+        ));
+        assert!(filtered(
+            &east_west,
+            TieDirection::North,
+            3,
+            TIESubtype::Prefix
+        ));
+    }
+
+    #[test]
+    fn test_request_filtered() {
+        let tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let filtered = |link_info: &LinkInfo, direction, originator, tie_type| {
+            let header = scoped_header(direction, originator, tie_type);
+            tie_fsm.is_request_filtered(link_info, &header)
+        };
+
+        // Towards the south, request all North TIEs, the peer's TIEs and all South Node TIEs.
+        let south = link_info(1, 0);
+        assert!(!filtered(
+            &south,
+            TieDirection::North,
+            3,
+            TIESubtype::Prefix
+        ));
+        assert!(!filtered(
+            &south,
+            TieDirection::South,
+            2,
+            TIESubtype::Prefix
+        ));
+        assert!(!filtered(&south, TieDirection::South, 3, TIESubtype::Node));
+        assert!(filtered(&south, TieDirection::South, 3, TIESubtype::Prefix));
+
+        // Towards the north, request only South TIEs.
+        let north = link_info(1, 2);
+        assert!(!filtered(
+            &north,
+            TieDirection::South,
+            3,
+            TIESubtype::Prefix
+        ));
+        assert!(filtered(&north, TieDirection::North, 2, TIESubtype::Node));
+
+        // East-West links use the North rules on the ToF and the South rules otherwise.
+        let tof = link_info(TOP_OF_FABRIC_LEVEL, TOP_OF_FABRIC_LEVEL);
+        assert!(filtered(&tof, TieDirection::North, 3, TIESubtype::Prefix));
+        let east_west = link_info(1, 1);
+        assert!(!filtered(
+            &east_west,
+            TieDirection::North,
+            3,
+            TIESubtype::Prefix
+        ));
+    }
+
+    fn system_ids(ids: &[i64]) -> BTreeSet<SystemID> {
+        ids.iter()
+            .map(|&id| SystemID::try_from(id).unwrap())
+            .collect()
+    }
+
+    /// Insert a Node TIE for `originator` at level 1 which has the given northbound neighbors.
+    fn insert_parent(ls_db: &mut LinkStateDatabase, originator: i64, grandparents: &[i64]) {
+        let neighbors = grandparents
+            .iter()
+            .map(|&id| {
+                let neighbor = encoding::NodeNeighborsTIEElement {
+                    level: 2,
+                    cost: None,
+                    link_ids: None,
+                    bandwidth: None,
+                };
+                (id, neighbor)
+            })
+            .collect();
+        ls_db.insert(&node_tie(originator, 1, neighbors), LIFETIME);
+    }
+
+    #[test]
+    fn test_flood_repeaters() {
+        let mut ls_db = LinkStateDatabase::new();
+        for parent in 10..14 {
+            insert_parent(&mut ls_db, parent, &[20, 21]);
+        }
+        // Parent 14 is the only one which covers grandparent 22.
+        insert_parent(&mut ls_db, 14, &[22]);
+        let root = SystemID::try_from(1).unwrap();
+        let parents = system_ids(&[10, 11, 12, 13, 14]);
+
+        let config = FloodReductionConfig::default();
+        let repeaters = flood_repeaters(&ls_db, root, &parents, config);
+        assert_eq!(repeaters.len(), 3);
+        assert!(repeaters.contains(&SystemID::try_from(14).unwrap()));
+        // The election is stable.
+        assert_eq!(flood_repeaters(&ls_db, root, &parents, config), repeaters);
+
+        let config = FloodReductionConfig {
+            redundancy: 1,
+            ..Default::default()
+        };
+        assert_eq!(flood_repeaters(&ls_db, root, &parents, config).len(), 2);
+
+        // A parent whose Node TIEs have not been received yet is always elected.
+        let fresh_parents = system_ids(&[10, 11, 12, 13, 14, 15]);
+        assert!(flood_repeaters(&ls_db, root, &fresh_parents, config)
+            .contains(&SystemID::try_from(15).unwrap()));
+
+        let config = FloodReductionConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(flood_repeaters(&ls_db, root, &parents, config), parents);
+    }
+
+    #[test]
+    fn test_flood_reduced() {
+        let mut tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let ls_db = LinkStateDatabase::new();
+        tie_fsm.set_flood_reduced_originators(system_ids(&[3]));
+
+        // North TIEs originated by the southbound neighbor 3 are not reflooded north, but North
+        // TIEs of other nodes and TIEs flooded over other links are unaffected.
+        let north = link_info(1, 2);
+        let reduced = scoped_header(TieDirection::North, 3, TIESubtype::Prefix);
+        assert!(tie_fsm.is_flood_filtered(&north, &ls_db, &reduced));
+        assert!(!tie_fsm.is_flood_filtered(
+            &north,
+            &ls_db,
+            &scoped_header(TieDirection::North, 4, TIESubtype::Prefix)
+        ));
+        let tof = link_info(TOP_OF_FABRIC_LEVEL, TOP_OF_FABRIC_LEVEL);
+        assert!(!tie_fsm.is_flood_filtered(&tof, &ls_db, &reduced));
+    }
+
+    #[test]
+    fn test_flood_filtered_east_west_leaf() {
+        let tie_fsm = TieStateMachine::new(RetransmitConfig::default());
+        let ls_db = LinkStateDatabase::new();
+
+        // Leaves flood their own North TIEs to each other, but not those of other nodes.
+        let east_west = link_info(common::LEAF_LEVEL as u8, common::LEAF_LEVEL as u8);
+        assert!(!tie_fsm.is_flood_filtered(
+            &east_west,
+            &ls_db,
+            &scoped_header(TieDirection::North, 1, TIESubtype::Node)
+        ));
+        assert!(tie_fsm.is_flood_filtered(
+            &east_west,
+            &ls_db,
+            &scoped_header(TieDirection::North, 3, TIESubtype::Prefix)
+        ));
+        // Nodes in between don't flood North TIEs East-West at all.
+        assert!(tie_fsm.is_flood_filtered(
+            &link_info(1, 1),
+            &ls_db,
+            &scoped_header(TieDirection::North, 1, TIESubtype::Prefix)
+        ));
     }
 }
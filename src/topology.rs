@@ -1,16 +1,18 @@
 use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
-use std::num::{NonZeroU32, NonZeroUsize};
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
 use crate::lie_exchange;
 use crate::models::common::{
-    DEFAULT_LIE_UDP_PORT, DEFAULT_TIE_UDP_FLOOD_PORT, LEAF_LEVEL, TOP_OF_FABRIC_LEVEL,
+    IPPrefixType, DEFAULT_LIE_UDP_PORT, DEFAULT_TIE_UDP_FLOOD_PORT, LEAF_LEVEL, TOP_OF_FABRIC_LEVEL,
 };
 use crate::packet::SecretKeyStore;
-use crate::wrapper::SystemID;
+use crate::tie_exchange::{FloodReductionConfig, RetransmitConfig};
+use crate::wrapper::{self, SystemID};
 
 // 224.0.0.120
 const DEFAULT_LIE_IPV4_MCAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 120);
@@ -127,6 +129,11 @@ impl TopologyDescription {
 ///     },
 /// },
 /// ```
+/// Additionally, the following fields are specific to this implementation:
+/// - `tie_retransmit_interval`: seconds to wait for a TIE to be acknowledged before resending it.
+/// - `tie_retransmit_attempts`: the maximum number of times a TIE is sent before giving up on it.
+/// - `bandwidth_weighted_ecmp`: if true, the next hops of each route are weighted by the bandwidth
+///   advertised for them in the Node TIEs when installed into the FIB. Defaults to false.
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct GlobalConstants {
     pub tx_src_address: Option<Ipv4Addr>,
@@ -136,6 +143,38 @@ pub struct GlobalConstants {
     pub flooding_reduction: Option<bool>,
     pub flooding_reduction_redundancy: Option<NonZeroUsize>,
     pub flooding_reduction_similarity: Option<usize>,
+    pub tie_retransmit_interval: Option<NonZeroU64>,
+    pub tie_retransmit_attempts: Option<NonZeroUsize>,
+    pub bandwidth_weighted_ecmp: Option<bool>,
+}
+
+impl GlobalConstants {
+    /// The TIE retransmission configuration, using the defaults for any unspecified values.
+    pub fn retransmit_config(&self) -> RetransmitConfig {
+        let default = RetransmitConfig::default();
+        RetransmitConfig {
+            interval: self
+                .tie_retransmit_interval
+                .map_or(default.interval, |secs| Duration::from_secs(secs.get())),
+            max_attempts: self
+                .tie_retransmit_attempts
+                .map_or(default.max_attempts, NonZeroUsize::get),
+        }
+    }
+
+    /// The flooding reduction configuration, using the defaults for any unspecified values.
+    pub fn flood_reduction_config(&self) -> FloodReductionConfig {
+        let default = FloodReductionConfig::default();
+        FloodReductionConfig {
+            enabled: self.flooding_reduction.unwrap_or(default.enabled),
+            redundancy: self
+                .flooding_reduction_redundancy
+                .map_or(default.redundancy, NonZeroUsize::get),
+            similarity: self
+                .flooding_reduction_similarity
+                .unwrap_or(default.similarity),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -221,6 +260,12 @@ pub struct NodeDescription {
     pub v4prefixes: Vec<V4Prefix>,
     #[serde(default)]
     pub v6prefixes: Vec<V6Prefix>,
+    /// The PoD advertised in LIEs. If not given, no PoD is advertised.
+    pub pod: Option<u32>,
+    /// The RIFT instance name advertised in LIEs.
+    pub instance_name: Option<String>,
+    /// The auto-EVPN fabric ID advertised in LIEs.
+    pub fabric_id: Option<u16>,
 }
 
 impl NodeDescription {
@@ -258,11 +303,19 @@ pub struct Interface {
     pub name: String,
     pub bandwidth: Option<usize>,
     pub metric: Option<NonZeroUsize>,
+    /// The L3 MTU of this interface, which must match the MTU of the neighbor. Must be non-zero and
+    /// fit in an `MTUSizeType`. Defaults to `default_mtu_size`.
+    pub mtu: Option<usize>,
     tx_lie_port: Option<u16>,
     rx_lie_port: Option<u16>,
     rx_tie_port: Option<u16>,
     #[serde(default = "default_false")]
     pub advertise_subnet: bool,
+    /// The subnet of this interface, which is advertised if `advertise_subnet` is set. Unlike
+    /// rift-python, which reads the subnet from the operating system, it must be configured here.
+    pub subnet: Option<Subnet>,
+    /// The label advertised to the neighbor in LIEs.
+    pub label: Option<u32>,
     pub active_key: Option<u8>,
     #[serde(default)]
     pub accept_keys: HashSet<u8>,
@@ -321,6 +374,24 @@ impl From<Level> for Option<lie_exchange::Level> {
     }
 }
 
+impl Level {
+    /// The leaf flags implied by this level. `leaf` makes the node leaf-only, and `leaf-to-leaf`
+    /// additionally enables the leaf-2-leaf procedures (4.3.9).
+    pub fn leaf_flags(&self) -> lie_exchange::LeafFlags {
+        match self {
+            Level::NamedLevel(NamedLevel::Leaf) => lie_exchange::LeafFlags {
+                leaf_only: true,
+                leaf_2_leaf: false,
+            },
+            Level::NamedLevel(NamedLevel::LeafToLeaf) => lie_exchange::LeafFlags {
+                leaf_only: true,
+                leaf_2_leaf: true,
+            },
+            _ => lie_exchange::LeafFlags::default(),
+        }
+    }
+}
+
 impl Default for Level {
     fn default() -> Self {
         Level::NamedLevel(NamedLevel::Undefined)
@@ -352,6 +423,13 @@ pub struct V4Prefix {
     pub metric: NonZeroUsize,
 }
 
+impl V4Prefix {
+    /// Returns this prefix with the host bits cleared, or None if the mask is too long.
+    pub fn ip_prefix(&self) -> Option<IPPrefixType> {
+        masked_prefix(self.address.into(), self.mask)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct V6Prefix {
     pub address: Ipv6Addr,
@@ -359,6 +437,46 @@ pub struct V6Prefix {
     pub metric: NonZeroUsize,
 }
 
+impl V6Prefix {
+    /// Returns this prefix with the host bits cleared, or None if the mask is too long.
+    pub fn ip_prefix(&self) -> Option<IPPrefixType> {
+        masked_prefix(self.address.into(), self.mask)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Subnet {
+    pub address: IpAddr,
+    pub mask: usize,
+}
+
+impl Subnet {
+    /// Returns the prefix of this subnet with the host bits cleared, or None if the mask is too
+    /// long.
+    pub fn ip_prefix(&self) -> Option<IPPrefixType> {
+        masked_prefix(self.address, self.mask)
+    }
+}
+
+/// Returns the prefix `address/mask` with the host bits of the address cleared (so that
+/// `10.0.0.1/24` becomes `10.0.0.0/24`), or None if the mask is too long for the address.
+fn masked_prefix(address: IpAddr, mask: usize) -> Option<IPPrefixType> {
+    let mask = u8::try_from(mask).ok()?;
+    let address = match address {
+        IpAddr::V4(address) => {
+            let host_bits = 32u32.checked_sub(mask.into())?;
+            let mask = u32::MAX.checked_shl(host_bits).unwrap_or(0);
+            IpAddr::from(Ipv4Addr::from(u32::from(address) & mask))
+        }
+        IpAddr::V6(address) => {
+            let host_bits = 128u32.checked_sub(mask.into())?;
+            let mask = u128::MAX.checked_shl(host_bits).unwrap_or(0);
+            IpAddr::from(Ipv6Addr::from(u128::from(address) & mask))
+        }
+    };
+    Some(wrapper::ip_prefix(address, mask))
+}
+
 // Serde calls a function when passing a default. These functions are just for convience since some
 // of the fields need to default to true.
 
@@ -371,9 +489,14 @@ const fn default_true() -> bool {
 
 #[cfg(test)]
 mod test {
-    use crate::topology::NamedLevel;
+    use std::{
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        num::NonZeroUsize,
+    };
+
+    use crate::{topology::NamedLevel, wrapper};
 
-    use super::Level;
+    use super::{Level, Subnet, V4Prefix, V6Prefix};
 
     #[test]
     fn test_serialize_level() {
@@ -395,4 +518,61 @@ mod test {
             serde_yaml::to_string(&Level::NamedLevel(NamedLevel::Undefined)).unwrap()
         );
     }
+
+    #[test]
+    fn test_subnet_ip_prefix() {
+        let subnet = |address: IpAddr, mask| Subnet { address, mask }.ip_prefix();
+        assert_eq!(
+            subnet(Ipv4Addr::new(10, 0, 1, 5).into(), 24),
+            Some(wrapper::ip_prefix(Ipv4Addr::new(10, 0, 1, 0).into(), 24))
+        );
+        assert_eq!(
+            subnet(Ipv4Addr::new(10, 0, 1, 5).into(), 0),
+            Some(wrapper::ip_prefix(Ipv4Addr::UNSPECIFIED.into(), 0))
+        );
+        assert_eq!(
+            subnet(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into(), 64),
+            Some(wrapper::ip_prefix(
+                Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0).into(),
+                64
+            ))
+        );
+        assert_eq!(subnet(Ipv4Addr::new(10, 0, 1, 5).into(), 33), None);
+        assert_eq!(subnet(Ipv4Addr::new(10, 0, 1, 5).into(), 256 + 24), None);
+    }
+
+    #[test]
+    fn test_prefix_ip_prefix() {
+        let metric = NonZeroUsize::MIN;
+        let v4prefix = |address, mask| {
+            V4Prefix {
+                address,
+                mask,
+                metric,
+            }
+            .ip_prefix()
+        };
+        assert_eq!(
+            v4prefix(Ipv4Addr::new(10, 0, 1, 5), 16),
+            Some(wrapper::ip_prefix(Ipv4Addr::new(10, 0, 0, 0).into(), 16))
+        );
+        assert_eq!(v4prefix(Ipv4Addr::new(10, 0, 1, 5), 33), None);
+
+        let v6prefix = |address, mask| {
+            V6Prefix {
+                address,
+                mask,
+                metric,
+            }
+            .ip_prefix()
+        };
+        assert_eq!(
+            v6prefix(Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 1), 48),
+            Some(wrapper::ip_prefix(
+                Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0).into(),
+                48
+            ))
+        );
+        assert_eq!(v6prefix(Ipv6Addr::LOCALHOST, 129), None);
+    }
 }
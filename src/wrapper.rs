@@ -1,6 +1,7 @@
 use std::{
     cmp::Ordering,
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
     time::{Duration, SystemTime},
 };
 
@@ -30,6 +31,15 @@ impl From<encoding::TIEPacket> for TIEPacket {
     }
 }
 
+impl From<TIEPacket> for encoding::TIEPacket {
+    fn from(value: TIEPacket) -> Self {
+        encoding::TIEPacket {
+            header: value.header.into(),
+            element: value.element,
+        }
+    }
+}
+
 /// TIRE packet
 #[derive(Clone, Debug)]
 pub struct TIREPacket {
@@ -44,6 +54,14 @@ impl From<encoding::TIREPacket> for TIREPacket {
     }
 }
 
+impl From<TIREPacket> for encoding::TIREPacket {
+    fn from(value: TIREPacket) -> Self {
+        encoding::TIREPacket {
+            headers: value.headers.into_iter().map(|x| x.into()).collect(),
+        }
+    }
+}
+
 /// TIDE with *sorted* TIE headers.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TIDEPacket {
@@ -64,7 +82,7 @@ const MIN_TIE_ID: encoding::TIEID = encoding::TIEID {
     tie_nr: 0,
 };
 
-const MAX_TIE_ID: encoding::TIEID = encoding::TIEID {
+pub(crate) const MAX_TIE_ID: encoding::TIEID = encoding::TIEID {
     direction: common::TieDirectionType::NORTH,
     originator: u64::MAX as common::SystemIDType,
     tietype: common::TIETypeType::T_I_E_TYPE_MAX_VALUE,
@@ -93,6 +111,16 @@ impl From<encoding::TIDEPacket> for TIDEPacket {
     }
 }
 
+impl From<TIDEPacket> for encoding::TIDEPacket {
+    fn from(value: TIDEPacket) -> Self {
+        encoding::TIDEPacket {
+            start_range: value.start_range.map_or(MIN_TIE_ID, |x| x.into()),
+            end_range: value.end_range.map_or(MAX_TIE_ID, |x| x.into()),
+            headers: value.headers.into_iter().map(|x| x.into()).collect(),
+        }
+    }
+}
+
 /// Header of a TIE as described in TIRE/TIDE.
 /// TODO: Is the default Ord implementation fine for this?
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -109,6 +137,25 @@ impl TIEHeaderWithLifetime {
             remaining_lifetime: common::DEFAULT_LIFETIME as LifetimeInSecs,
         }
     }
+
+    /// Compare the age of two TIE headers. This compares the headers first, and if the headers are
+    /// equal, then the remaining lifetimes are compared, with the longer lifetime considered newer.
+    /// From the spec: TIEs with lifetimes differing by less than `lifetime_diff2ignore` MUST be
+    /// considered EQUAL (if all other fields are equal).
+    /// Note that this is distinct from the [Ord] implementation, which compares the lifetimes exactly.
+    pub fn compare(&self, other: &TIEHeaderWithLifetime) -> Ordering {
+        match self.header.cmp(&other.header) {
+            Ordering::Equal => {
+                let difference = self.remaining_lifetime.abs_diff(other.remaining_lifetime);
+                if difference < common::LIFETIME_DIFF2IGNORE as LifetimeInSecs {
+                    Ordering::Equal
+                } else {
+                    self.remaining_lifetime.cmp(&other.remaining_lifetime)
+                }
+            }
+            x => x,
+        }
+    }
 }
 
 impl From<encoding::TIEHeaderWithLifeTime> for TIEHeaderWithLifetime {
@@ -201,6 +248,45 @@ impl From<TIEHeader> for encoding::TIEHeader {
     }
 }
 
+/// Convert an address and prefix length into the Thrift representation of an IP prefix.
+pub fn ip_prefix(address: IpAddr, prefixlen: u8) -> common::IPPrefixType {
+    match address {
+        IpAddr::V4(address) => common::IPPrefixType::Ipv4prefix(common::IPv4PrefixType {
+            address: u32::from(address) as common::IPv4Address,
+            prefixlen: prefixlen as common::PrefixLenType,
+        }),
+        IpAddr::V6(address) => common::IPPrefixType::Ipv6prefix(common::IPv6PrefixType {
+            address: address.octets().to_vec(),
+            prefixlen: prefixlen as common::PrefixLenType,
+        }),
+    }
+}
+
+/// Create a Node TIE element with the given level and neighbors, leaving every optional field
+/// unset.
+pub fn node_element(
+    level: common::LevelType,
+    neighbors: BTreeMap<common::SystemIDType, encoding::NodeNeighborsTIEElement>,
+) -> encoding::NodeTIEElement {
+    encoding::NodeTIEElement::new(
+        level,
+        neighbors,
+        encoding::NodeCapabilities::new(encoding::PROTOCOL_MINOR_VERSION, None, None, None, None),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+}
+
 /// Wrapper since the values need to be unsigned and the Thrift autogenerated code is not unsigned.
 /// Timestamp per IEEE 802.1AS, all values MUST be interpreted in
 /// implementation as unsigned.
@@ -210,6 +296,19 @@ pub struct IEEE8021ASTimeStamp {
     pub a_s_nsec: Option<u32>,
 }
 
+impl IEEE8021ASTimeStamp {
+    /// The current time, as measured from the UNIX epoch.
+    pub fn now() -> IEEE8021ASTimeStamp {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        IEEE8021ASTimeStamp {
+            a_s_sec: now.as_secs(),
+            a_s_nsec: Some(now.subsec_nanos()),
+        }
+    }
+}
+
 impl TryFrom<IEEE8021ASTimeStamp> for SystemTime {
     type Error = IEEE8021ASTimeStampError;
 
@@ -408,6 +507,12 @@ impl TryFrom<common::TIENrType> for TieNumber {
         }
     }
 }
+impl TieNumber {
+    /// The TIE number of every TIE originated by this node, since TIEs are never split across
+    /// several TIE numbers.
+    pub const FIRST: TieNumber = TieNumber(1);
+}
+
 impl From<TieNumber> for common::TIENrType {
     fn from(value: TieNumber) -> Self {
         value.0 as common::TIENrType